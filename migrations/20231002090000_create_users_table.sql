@@ -0,0 +1,6 @@
+CREATE TABLE users(
+    user_id uuid PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    /* Argon2id hash in PHC string format, it includes the salt and the parameters. */
+    password_hash TEXT NOT NULL
+);
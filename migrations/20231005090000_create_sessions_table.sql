@@ -0,0 +1,11 @@
+CREATE TABLE sessions(
+    session_id TEXT PRIMARY KEY,
+    user_id uuid NOT NULL
+    /* Logging out a user everywhere is as simple as deleting the user. */
+    REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    /* Absolute timeout, the session ends at this time even if it is in use. */
+    expires_at timestamptz NOT NULL
+);
+CREATE INDEX sessions_expires_at_idx ON sessions (expires_at);
@@ -0,0 +1,14 @@
+CREATE TABLE api_keys(
+    api_key_id uuid PRIMARY KEY,
+    name TEXT NOT NULL,
+    /* The first characters of the key, to tell keys apart in listings. */
+    prefix TEXT NOT NULL,
+    /* SHA-256 of the key. The key itself is only shown once, when it is created. */
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_by uuid NULL
+    REFERENCES users (user_id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz NULL,
+    revoked_at timestamptz NULL
+);
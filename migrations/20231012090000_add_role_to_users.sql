@@ -0,0 +1,6 @@
+-- Existing users keep all their rights, new users start as viewers.
+BEGIN;
+    ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+        CHECK (role IN ('owner', 'editor', 'viewer'));
+    ALTER TABLE users ALTER COLUMN role SET DEFAULT 'viewer';
+COMMIT;
@@ -0,0 +1,19 @@
+BEGIN;
+    /* The secret is encrypted with AES-256-GCM: a 12 byte nonce followed by the ciphertext. */
+    ALTER TABLE users ADD COLUMN totp_secret BYTEA;
+    /* NULL while enrolment has not been confirmed with a first code. */
+    ALTER TABLE users ADD COLUMN totp_enabled_at timestamptz;
+    /* The last accepted 30 second time step, so a code cannot be used twice. */
+    ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
+
+    CREATE TABLE recovery_codes(
+        user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+        code_hash TEXT NOT NULL,
+        used_at timestamptz,
+        PRIMARY KEY (user_id, code_hash)
+    );
+
+    /* A session that passed the password check but not yet the second factor. */
+    ALTER TABLE sessions ADD COLUMN mfa_pending BOOLEAN NOT NULL DEFAULT false;
+    ALTER TABLE sessions ADD COLUMN mfa_failed_attempts INTEGER NOT NULL DEFAULT 0;
+COMMIT;
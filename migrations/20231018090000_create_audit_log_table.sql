@@ -0,0 +1,34 @@
+BEGIN;
+    CREATE TABLE audit_log(
+        audit_id BIGSERIAL PRIMARY KEY,
+        occurred_at timestamptz NOT NULL,
+        /* No foreign key: entries outlive the users they mention. */
+        actor_id uuid,
+        /* The username, or the one that was tried for failed logins. */
+        actor_name TEXT NOT NULL,
+        action TEXT NOT NULL,
+        target_type TEXT,
+        target_id TEXT,
+        request_id TEXT,
+        ip TEXT,
+        details JSONB
+    );
+    CREATE INDEX audit_log_actor_id_idx ON audit_log (actor_id, audit_id);
+    CREATE INDEX audit_log_action_idx ON audit_log (action, audit_id);
+    CREATE INDEX audit_log_target_idx ON audit_log (target_type, target_id, audit_id);
+    CREATE INDEX audit_log_occurred_at_idx ON audit_log (occurred_at);
+
+    /* Append-only: entries cannot be changed or removed, not even by the application. */
+    CREATE FUNCTION audit_log_is_append_only() RETURNS trigger AS $$
+    BEGIN
+        RAISE EXCEPTION 'audit_log is append-only';
+    END;
+    $$ LANGUAGE plpgsql;
+
+    CREATE TRIGGER audit_log_no_update_or_delete
+        BEFORE UPDATE OR DELETE ON audit_log
+        FOR EACH ROW EXECUTE FUNCTION audit_log_is_append_only();
+    CREATE TRIGGER audit_log_no_truncate
+        BEFORE TRUNCATE ON audit_log
+        FOR EACH STATEMENT EXECUTE FUNCTION audit_log_is_append_only();
+COMMIT;
@@ -0,0 +1,8 @@
+-- Indexes for GET /admin/subscribers: keyset pagination over each sort order,
+-- with and without a status filter, and substring search on email and name.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX subscriptions_subscribed_at_idx ON subscriptions (subscribed_at, id);
+CREATE INDEX subscriptions_status_subscribed_at_idx ON subscriptions (status, subscribed_at, id);
+CREATE INDEX subscriptions_name_idx ON subscriptions (name, id);
+CREATE INDEX subscriptions_email_trgm_idx ON subscriptions USING gin (email gin_trgm_ops);
+CREATE INDEX subscriptions_name_trgm_idx ON subscriptions USING gin (name gin_trgm_ops);
@@ -0,0 +1,9 @@
+BEGIN;
+    /* One row per worker process, updated while its jobs run. */
+    CREATE TABLE worker_heartbeats(
+        worker_id uuid PRIMARY KEY,
+        started_at timestamptz NOT NULL,
+        beat_at timestamptz NOT NULL
+    );
+    CREATE INDEX worker_heartbeats_beat_at_idx ON worker_heartbeats (beat_at);
+COMMIT;
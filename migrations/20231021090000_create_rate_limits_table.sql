@@ -0,0 +1,9 @@
+BEGIN;
+    /* Request counts of the current window, per limit and client or email. */
+    CREATE TABLE rate_limits(
+        key text PRIMARY KEY,
+        hits integer NOT NULL,
+        expires_at timestamptz NOT NULL
+    );
+    CREATE INDEX rate_limits_expires_at_idx ON rate_limits (expires_at);
+COMMIT;
@@ -0,0 +1,13 @@
+BEGIN;
+    /* A new address of an admin, stored once the link sent to it is followed. */
+    CREATE TABLE email_change_tokens(
+        /* SHA-256 of the token, the token itself is only in the email. */
+        token_hash TEXT PRIMARY KEY,
+        user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+        email TEXT NOT NULL,
+        created_at timestamptz NOT NULL,
+        expires_at timestamptz NOT NULL,
+        used_at timestamptz
+    );
+    CREATE INDEX email_change_tokens_user_id_idx ON email_change_tokens (user_id);
+COMMIT;
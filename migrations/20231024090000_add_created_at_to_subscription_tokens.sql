@@ -0,0 +1,3 @@
+-- When each confirmation token was issued, so admins see how old a pending link is
+-- without seeing the token. Earlier tokens count as issued now.
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
@@ -1,13 +1,29 @@
-use axum::routing::{get, post};
+use axum::middleware::from_fn_with_state;
+use axum::routing::{delete, get, post, put};
 use axum::Router;
 use axum_macros::FromRef;
 
+use crate::authentication::{MfaConfig, SessionConfig};
 use crate::configuration::Settings;
-use crate::email_client::{EmailClient, ValidEmail};
+use crate::email_client::EmailClient;
+use crate::hardening::HttpConfig;
+use crate::metrics::{self, HttpMetricsLayer};
+use crate::rate_limit::{self, RateLimiter};
+use crate::reload::RuntimeSettings;
+use crate::request_id::{self, MakeRequestSpan};
+use crate::routes::admin;
+use crate::routes::api;
 use crate::routes::confirm::confirm_subscription;
+use crate::routes::health;
+use crate::routes::login::{login, login_mfa, logout};
+use crate::routes::pages;
+use crate::routes::password::{forgot_password, reset_password};
 use crate::routes::subscribe::subscribe;
 use crate::routes::utils::health_check;
 
+use arc_swap::ArcSwap;
+use metrics_exporter_prometheus::PrometheusHandle;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 use std::sync::Arc;
 use tower_http::trace;
@@ -19,25 +35,60 @@ pub struct AppState {
     pub pg_pool: PgPool,
     pub email_client: EmailClient,
     pub base_url: ApplicationBaseUrl,
+    pub sessions: SessionConfig,
+    pub mfa: MfaConfig,
+    pub metrics: PrometheusHandle,
+    pub metrics_token: Option<Secret<String>>,
+    pub http: HttpConfig,
+    pub rate_limiter: RateLimiter,
+    pub email_provider_check: health::EmailProviderCheck,
+    // Settings that can change while the app is running, see `reload`.
+    pub runtime: Arc<ArcSwap<RuntimeSettings>>,
 }
 
 #[derive(Clone, FromRef)]
 pub struct ApplicationBaseUrl(pub String);
 
 pub async fn spawn_app(configuration: Settings) -> Result<Router, String> {
+    let shared_state = build_state(configuration).await?;
+    Ok(router(shared_state))
+}
+
+// Create the state shared by all handlers: connection pool, email client, etc.
+pub async fn build_state(configuration: Settings) -> Result<Arc<AppState>, String> {
+    let runtime = RuntimeSettings::try_from(&configuration)?;
+    let sessions = SessionConfig::try_from(&configuration.session)?;
+    let mfa = MfaConfig::try_from(&configuration.mfa)?;
+    let http = HttpConfig::try_from(&configuration.http)?;
+
     tracing::info!("Creating Postgres connection pool.");
-    let pg_pool = PgPool::connect_lazy_with(configuration.database.with_db());
+    let pool_options = configuration.database.pool_options();
+    tracing::info!(
+        max_connections = pool_options.get_max_connections(),
+        min_connections = pool_options.get_min_connections(),
+        acquire_timeout = ?pool_options.get_acquire_timeout(),
+        idle_timeout = ?pool_options.get_idle_timeout(),
+        max_lifetime = ?pool_options.get_max_lifetime(),
+        statement_timeout = ?configuration.database.pool.statement_timeout(),
+        application_name = %configuration.database.pool.application_name,
+        "Effective connection pool configuration."
+    );
+    let pg_pool = pool_options.connect_lazy_with(configuration.database.with_db());
 
-    let sender_email = ValidEmail::new(&configuration.email_client.sender)?;
+    let runtime = Arc::new(ArcSwap::from_pointee(runtime));
     let timeout = configuration.email_client.timeout();
     let email_client = EmailClient::new(
         configuration.email_client.base_url,
-        sender_email,
+        runtime.clone(),
         configuration.email_client.authorization_token,
         timeout,
     );
 
     let base_url = ApplicationBaseUrl(configuration.application.host);
+    let metrics_token = configuration
+        .metrics
+        .bearer_token
+        .filter(|token| !token.expose_secret().is_empty());
 
     // Axum starts a service per thread on the machine.
     // Arc lets the database connection be shared between threads
@@ -45,20 +96,149 @@ pub async fn spawn_app(configuration: Settings) -> Result<Router, String> {
         pg_pool,
         email_client,
         base_url,
+        sessions,
+        mfa,
+        metrics: metrics::prometheus_handle(),
+        metrics_token,
+        http,
+        rate_limiter: RateLimiter::default(),
+        email_provider_check: Default::default(),
+        runtime,
     });
 
+    Ok(shared_state)
+}
+
+pub fn router(shared_state: Arc<AppState>) -> Router {
     // build our application with some routes
     tracing::info!("Spawning app.");
-    let app = Router::new()
+    let http = shared_state.http.clone();
+    let routes = Router::new()
         .route("/health_check", get(health_check))
-        .route("/subscribe", post(subscribe))
-        .route("/confirm", post(confirm_subscription))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/metrics", get(metrics::metrics))
+        // Called by the signup forms embedded in other sites.
+        .route(
+            "/subscribe",
+            post(subscribe)
+                .route_layer(from_fn_with_state(
+                    shared_state.clone(),
+                    rate_limit::limit_subscribe,
+                ))
+                .layer(http.cors()),
+        )
+        .route(
+            "/confirm",
+            post(confirm_subscription)
+                .route_layer(from_fn_with_state(
+                    shared_state.clone(),
+                    rate_limit::limit_confirm,
+                ))
+                .layer(http.cors()),
+        )
+        .route("/login", post(login))
+        .route("/login/mfa", post(login_mfa))
+        .route("/logout", post(logout))
+        .route("/password/forgot", post(forgot_password))
+        // The reset email links to the page, whose form posts to the dashboard.
+        .route(
+            "/password/reset",
+            get(pages::password::reset_password_page).post(reset_password),
+        )
+        .route("/email/confirm", get(pages::email::confirm_email_page))
+        // Admin routes are protected by the `AuthenticatedUser` and `Authorized<P>`
+        // extractors in their handlers.
+        .route("/admin/me", get(admin::me))
+        .route("/admin/me/password", put(admin::account::change_password))
+        .route("/admin/me/email", put(admin::account::set_email))
+        .route("/admin/mfa/totp", post(admin::mfa::begin_totp_enrolment))
+        .route(
+            "/admin/mfa/totp/confirm",
+            post(admin::mfa::confirm_totp_enrolment),
+        )
+        .route(
+            "/admin/api_keys",
+            get(admin::api_keys::list_api_keys).post(admin::api_keys::create_api_key),
+        )
+        .route(
+            "/admin/api_keys/:api_key_id",
+            delete(admin::api_keys::revoke_api_key),
+        )
+        .route("/admin/users/:user_id/role", put(admin::users::set_role))
+        .route("/admin/audit_log", get(admin::audit_log::list_audit_log))
+        .route(
+            "/admin/log_level",
+            get(admin::logging::get_log_level).put(admin::logging::set_log_level),
+        )
+        .route(
+            "/admin/subscribers",
+            get(admin::subscribers::list_subscribers),
+        )
+        .route(
+            "/admin/subscribers/:subscriber_id",
+            get(admin::subscribers::get_subscriber)
+                .patch(admin::subscribers::update_subscriber)
+                .delete(admin::subscribers::delete_subscriber),
+        )
+        .route(
+            "/admin/subscribers/:subscriber_id/confirm",
+            post(admin::subscribers::confirm_subscriber),
+        )
+        .route(
+            "/admin/subscribers/:subscriber_id/unsubscribe",
+            post(admin::subscribers::unsubscribe_subscriber),
+        )
+        .route(
+            "/admin/subscribers/:subscriber_id/resend_confirmation",
+            post(admin::subscribers::resend_confirmation),
+        )
+        // HTML pages for the admins who do not use the JSON API. They redirect to
+        // the login page instead of answering 401.
+        .route("/dashboard", get(pages::dashboard::dashboard))
+        .route(
+            "/dashboard/login",
+            get(pages::login::login_page).post(pages::login::login),
+        )
+        .route(
+            "/dashboard/login/mfa",
+            get(pages::login::login_mfa_page).post(pages::login::login_mfa),
+        )
+        .route("/dashboard/logout", post(pages::login::logout))
+        .route(
+            "/dashboard/email/confirm",
+            post(pages::email::confirm_email_change),
+        )
+        .route(
+            "/dashboard/password/reset",
+            post(pages::password::reset_password),
+        )
+        .route(
+            "/dashboard/subscribers",
+            get(pages::subscribers::subscribers_page),
+        )
+        // API routes are protected by the `ApiClient` extractor, for server-to-server calls.
+        .route("/api/me", get(api::me))
+        .route("/api/subscribers", get(api::list_subscribers))
+        .route(
+            "/api/subscribers/:subscriber_id",
+            get(api::get_subscriber).patch(api::update_subscriber),
+        )
+        .route(
+            "/api/subscribers/:subscriber_id/unsubscribe",
+            post(api::unsubscribe_subscriber),
+        );
+
+    http.apply(routes)
+        .layer(HttpMetricsLayer)
         .layer(
             TraceLayer::new_for_http()
-                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .make_span_with(MakeRequestSpan)
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
         )
-        .with_state(shared_state);
-
-    Ok(app)
+        // Outside of the trace layer, so the id is set before the span is made and
+        // every response gets it.
+        .layer(request_id::propagate_request_id_layer())
+        .layer(request_id::set_request_id_layer())
+        .with_state(shared_state)
 }
@@ -0,0 +1,223 @@
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app::AppState;
+use crate::authentication::AuthenticatedUser;
+use crate::rate_limit;
+use crate::redact;
+use crate::request_id::RequestId;
+
+/// Where a request came from, recorded with every audit entry.
+/// The IP is the client, resolved like for rate limiting, see `rate_limit::client_ip`.
+/// It is only known when the server is run with `ConnectInfo<SocketAddr>`.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub request_id: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuditContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .map(|value| value.chars().take(200).collect());
+        let peer = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let runtime = state.runtime.load();
+        let ip = rate_limit::client_ip(peer, &parts.headers, &runtime.rate_limits.trusted_proxies)
+            .map(|ip| ip.to_string());
+        Ok(Self { request_id, ip })
+    }
+}
+
+/// Who did it: a logged in admin, or someone who tried to log in as `name`.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub user_id: Option<Uuid>,
+    pub name: String,
+}
+
+impl Actor {
+    pub fn user(user_id: Uuid, username: &str) -> Self {
+        Self {
+            user_id: Some(user_id),
+            name: username.to_string(),
+        }
+    }
+
+    pub fn from_user(user: &AuthenticatedUser) -> Self {
+        Self::user(user.user_id, &user.username)
+    }
+
+    /// A client of the `/api` routes.
+    pub fn api_key(api_key_id: Uuid) -> Self {
+        Self {
+            user_id: None,
+            name: format!("api_key:{}", api_key_id),
+        }
+    }
+
+    /// Something done outside the API, e.g. `Actor::system("cli")`.
+    pub fn system(name: &str) -> Self {
+        Self {
+            user_id: None,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn anonymous(attempted_username: &str) -> Self {
+        Self {
+            user_id: None,
+            name: attempted_username.to_string(),
+        }
+    }
+}
+
+/// One thing that happened, e.g. `AuditEvent::new("subscriber.delete").target("subscriber", subscriber_id)`.
+#[derive(Debug)]
+pub struct AuditEvent {
+    action: &'static str,
+    target_type: Option<&'static str>,
+    target_id: Option<String>,
+    details: Option<Value>,
+}
+
+impl AuditEvent {
+    pub fn new(action: &'static str) -> Self {
+        Self {
+            action,
+            target_type: None,
+            target_id: None,
+            details: None,
+        }
+    }
+
+    pub fn target(mut self, target_type: &'static str, target_id: impl ToString) -> Self {
+        self.target_type = Some(target_type);
+        self.target_id = Some(target_id.to_string());
+        self
+    }
+
+    pub fn details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Append an entry to the audit log. Pass the transaction of the change being
+/// recorded, so the change is not stored without its entry. Changes that cannot share
+/// a transaction, e.g. an email sent, fail their request instead.
+#[tracing::instrument(name = "Record audit event", skip(executor, context, actor))]
+pub async fn record<'c>(
+    executor: impl PgExecutor<'c>,
+    context: &AuditContext,
+    actor: &Actor,
+    event: AuditEvent,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log
+            (occurred_at, actor_id, actor_name, action, target_type, target_id, request_id, ip, details)
+        VALUES (now(), $1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        actor.user_id,
+        actor.name,
+        event.action,
+        event.target_type,
+        event.target_id,
+        context.request_id,
+        context.ip,
+        event.details,
+    )
+    .execute(executor)
+    .await
+    .inspect_err(|e| {
+        tracing::error!(
+            action = event.action,
+            "Failed to write audit log: {}",
+            redact::DbError(e)
+        )
+    })?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub audit_id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor_id: Option<Uuid>,
+    pub actor_name: String,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub request_id: Option<String>,
+    pub ip: Option<String>,
+    pub details: Option<Value>,
+}
+
+/// Filters for `list_entries`. Unset filters match everything.
+#[derive(Debug, Default)]
+pub struct AuditFilter<'a> {
+    pub actor_id: Option<Uuid>,
+    pub action: Option<&'a str>,
+    pub target_type: Option<&'a str>,
+    pub target_id: Option<&'a str>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Newest entries first, at most `limit`, older than `before` (an `audit_id`) if given.
+#[tracing::instrument(name = "List audit log entries", skip(pool))]
+pub async fn list_entries(
+    pool: &PgPool,
+    filter: &AuditFilter<'_>,
+    before: Option<i64>,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        AuditEntry,
+        r#"
+        SELECT audit_id, occurred_at, actor_id, actor_name, action, target_type, target_id,
+            request_id, ip, details
+        FROM audit_log
+        WHERE ($1::uuid IS NULL OR actor_id = $1)
+            AND ($2::text IS NULL OR action = $2)
+            AND ($3::text IS NULL OR target_type = $3)
+            AND ($4::text IS NULL OR target_id = $4)
+            AND ($5::timestamptz IS NULL OR occurred_at >= $5)
+            AND ($6::timestamptz IS NULL OR occurred_at < $6)
+            AND ($7::bigint IS NULL OR audit_id < $7)
+        ORDER BY audit_id DESC
+        LIMIT $8
+        "#,
+        filter.actor_id,
+        filter.action,
+        filter.target_type,
+        filter.target_id,
+        filter.since,
+        filter.until,
+        before,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
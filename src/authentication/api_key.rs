@@ -0,0 +1,237 @@
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app::AppState;
+use crate::authentication::AuthError;
+use crate::token::hash_token;
+
+const KEY_PREFIX: &str = "z2p_";
+
+/// What an API key is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "subscribers:read")]
+    SubscribersRead,
+    #[serde(rename = "subscribers:write")]
+    SubscribersWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::SubscribersRead => "subscribers:read",
+            Scope::SubscribersWrite => "subscribers:write",
+        }
+    }
+}
+
+impl TryFrom<&str> for Scope {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "subscribers:read" => Ok(Self::SubscribersRead),
+            "subscribers:write" => Ok(Self::SubscribersWrite),
+            other => Err(format!("{} is not a known scope.", other)),
+        }
+    }
+}
+
+/// A client that authenticated with a valid, unrevoked API key in an
+/// `Authorization: Bearer <key>` header. Add it as a handler argument and
+/// call `require_scope` to protect a route.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    pub api_key_id: Uuid,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiClient {
+    pub fn require_scope(&self, scope: Scope) -> Result<(), AuthError> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(AuthError::MissingScope(scope))
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ApiClient {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let key = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthError::MissingApiKey)?;
+        authenticate_api_key(&state.pg_pool, key.trim())
+            .await?
+            .ok_or(AuthError::InvalidApiKey)
+    }
+}
+
+/// Look up an unrevoked key and record that it was used.
+#[tracing::instrument(
+    name = "Authenticate API key",
+    skip(pool, key),
+    fields(api_key_id = tracing::field::Empty)
+)]
+async fn authenticate_api_key(pool: &PgPool, key: &str) -> Result<Option<ApiClient>, AuthError> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_keys SET last_used_at = now()
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        RETURNING api_key_id, scopes
+        "#,
+        hash_token(key),
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    // Scopes that were removed from the code since the key was made are ignored.
+    let scopes = row
+        .scopes
+        .iter()
+        .filter_map(|s| Scope::try_from(s.as_str()).ok())
+        .collect();
+    tracing::Span::current().record("api_key_id", tracing::field::display(&row.api_key_id));
+    Ok(Some(ApiClient {
+        api_key_id: row.api_key_id,
+        scopes,
+    }))
+}
+
+/// An API key as shown to admins. The key itself is not stored.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyInfo {
+    pub api_key_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Create a key with the given scopes. Returns its id and the key, which cannot be recovered later.
+#[tracing::instrument(name = "Create API key", skip(transaction))]
+pub async fn create_api_key(
+    transaction: &mut Transaction<'_, Postgres>,
+    name: &str,
+    scopes: &[Scope],
+    created_by: Uuid,
+) -> Result<(Uuid, Secret<String>), sqlx::Error> {
+    let api_key_id = Uuid::new_v4();
+    let key = generate_api_key();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_keys (api_key_id, name, prefix, key_hash, scopes, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        "#,
+        api_key_id,
+        name,
+        &key.expose_secret()[..KEY_PREFIX.len() + 6],
+        hash_token(key.expose_secret()),
+        &scopes,
+        created_by,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok((api_key_id, key))
+}
+
+#[tracing::instrument(name = "List API keys", skip(pool))]
+pub async fn list_api_keys(pool: &PgPool) -> Result<Vec<ApiKeyInfo>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyInfo,
+        r#"
+        SELECT api_key_id, name, prefix, scopes, created_by, created_at, last_used_at, revoked_at
+        FROM api_keys ORDER BY created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Revoke a key. Returns false if there is no such key, or it was already revoked.
+#[tracing::instrument(name = "Revoke API key", skip(transaction))]
+pub async fn revoke_api_key(
+    transaction: &mut Transaction<'_, Postgres>,
+    api_key_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE api_keys SET revoked_at = now() WHERE api_key_id = $1 AND revoked_at IS NULL"#,
+        api_key_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+fn generate_api_key() -> Secret<String> {
+    let random: String = thread_rng()
+        .sample_iter(Alphanumeric)
+        .map(char::from)
+        .take(40)
+        .collect();
+    Secret::new(format!("{}{}", KEY_PREFIX, random))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn scopes_round_trip_through_their_names() {
+        for scope in [Scope::SubscribersRead, Scope::SubscribersWrite] {
+            assert_eq!(Scope::try_from(scope.as_str()), Ok(scope));
+            assert_eq!(
+                serde_json::to_string(&scope).unwrap(),
+                format!("\"{}\"", scope.as_str())
+            );
+        }
+        assert_err!(Scope::try_from("subscribers:delete"));
+    }
+
+    #[test]
+    fn require_scope_only_accepts_granted_scopes() {
+        let client = ApiClient {
+            api_key_id: Uuid::new_v4(),
+            scopes: vec![Scope::SubscribersRead],
+        };
+        assert_ok!(client.require_scope(Scope::SubscribersRead));
+        assert_err!(client.require_scope(Scope::SubscribersWrite));
+    }
+
+    #[test]
+    fn generated_keys_are_unique_and_prefixed() {
+        let first = generate_api_key();
+        let second = generate_api_key();
+        assert!(first.expose_secret().starts_with(KEY_PREFIX));
+        assert_ne!(first.expose_secret(), second.expose_secret());
+        assert_ne!(
+            hash_token(first.expose_secret()),
+            hash_token(second.expose_secret())
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::app::AppState;
+use crate::authentication::{AuthError, AuthenticatedUser};
+
+/// What an admin user is allowed to do, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::ListSubscribers => true,
+            Permission::EditSubscribers => matches!(self, Role::Editor | Role::Owner),
+            Permission::DeleteSubscribers
+            | Permission::ManageApiKeys
+            | Permission::ManageUsers
+            | Permission::ViewAuditLog
+            | Permission::ManageLogging => matches!(self, Role::Owner),
+        }
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "viewer" => Ok(Self::Viewer),
+            "editor" => Ok(Self::Editor),
+            "owner" => Ok(Self::Owner),
+            other => Err(format!("{} is not a known role.", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ListSubscribers,
+    EditSubscribers,
+    DeleteSubscribers,
+    ManageApiKeys,
+    ManageUsers,
+    ViewAuditLog,
+    ManageLogging,
+}
+
+impl AuthenticatedUser {
+    /// Fail with a 403 (and log it) if the user's role does not grant `permission`.
+    pub fn require_permission(&self, permission: Permission) -> Result<(), AuthError> {
+        if self.role.can(permission) {
+            Ok(())
+        } else {
+            tracing::warn!(
+                user_id = %self.user_id,
+                role = self.role.as_str(),
+                ?permission,
+                "Permission denied."
+            );
+            Err(AuthError::Forbidden(permission))
+        }
+    }
+}
+
+/// Ties a marker type to a `Permission`, for use with `Authorized`.
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+macro_rules! permission_markers {
+    ($($name:ident),*) => {
+        /// Marker types for `Authorized<P>`, one per `Permission`.
+        pub mod permissions {
+            $(
+                pub struct $name;
+                impl super::RequiredPermission for $name {
+                    const PERMISSION: super::Permission = super::Permission::$name;
+                }
+            )*
+        }
+    };
+}
+
+permission_markers!(
+    ListSubscribers,
+    EditSubscribers,
+    DeleteSubscribers,
+    ManageApiKeys,
+    ManageUsers,
+    ViewAuditLog,
+    ManageLogging
+);
+
+/// A logged-in admin whose role grants `P`, e.g. `Authorized<permissions::DeleteSubscribers>`.
+/// Requests without a session get a 401, users without the permission a 403.
+pub struct Authorized<P> {
+    pub user: AuthenticatedUser,
+    permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<P> FromRequestParts<Arc<AppState>> for Authorized<P>
+where
+    P: RequiredPermission,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+        user.require_permission(P::PERMISSION)?;
+        Ok(Self {
+            user,
+            permission: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewers_can_only_read() {
+        let role = Role::Viewer;
+        assert!(role.can(Permission::ListSubscribers));
+        assert!(!role.can(Permission::EditSubscribers));
+        assert!(!role.can(Permission::DeleteSubscribers));
+    }
+
+    #[test]
+    fn editors_can_edit_but_not_delete() {
+        let role = Role::Editor;
+        assert!(role.can(Permission::ListSubscribers));
+        assert!(role.can(Permission::EditSubscribers));
+        assert!(!role.can(Permission::DeleteSubscribers));
+        assert!(!role.can(Permission::ManageApiKeys));
+    }
+
+    #[test]
+    fn owners_can_do_everything() {
+        let role = Role::Owner;
+        for permission in [
+            Permission::ListSubscribers,
+            Permission::EditSubscribers,
+            Permission::DeleteSubscribers,
+            Permission::ManageApiKeys,
+            Permission::ManageUsers,
+            Permission::ViewAuditLog,
+            Permission::ManageLogging,
+        ] {
+            assert!(role.can(permission), "owner cannot {:?}", permission);
+        }
+    }
+
+    #[test]
+    fn roles_round_trip_through_their_names() {
+        for role in [Role::Viewer, Role::Editor, Role::Owner] {
+            assert_eq!(Role::try_from(role.as_str()), Ok(role));
+        }
+        assert!(Role::try_from("admin").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{Duration, Utc};
+use secrecy::Secret;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::token::{generate_token, hash_token};
+
+const EMAIL_CHANGE_TOKEN_LIFETIME_MINUTES: i64 = 60;
+
+/// Create a single-use token that sets `email` as the address of the user.
+#[tracing::instrument(name = "Create email change token", skip(pool, email))]
+pub async fn create_email_change_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    email: &str,
+) -> Result<Secret<String>, sqlx::Error> {
+    let token = generate_token();
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO email_change_tokens (token_hash, user_id, email, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        hash_token(&token),
+        user_id,
+        email,
+        now,
+        now + Duration::minutes(EMAIL_CHANGE_TOKEN_LIFETIME_MINUTES),
+    )
+    .execute(pool)
+    .await?;
+    Ok(Secret::new(token))
+}
+
+/// Store the address of an email change token, in the transaction of the caller. The
+/// token and any other outstanding tokens of the user are invalidated. Returns the id
+/// and name of the user, or None if the token is unknown, used or expired.
+#[tracing::instrument(name = "Confirm email change", skip(transaction, token))]
+pub async fn confirm_email_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE email_change_tokens SET used_at = now()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        RETURNING user_id, email
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let username = sqlx::query_scalar!(
+        r#"UPDATE users SET email = $1 WHERE user_id = $2 RETURNING username"#,
+        row.email,
+        row.user_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM email_change_tokens WHERE user_id = $1 AND used_at IS NULL"#,
+        row.user_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(Some((row.user_id, username)))
+}
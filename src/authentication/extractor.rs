@@ -0,0 +1,34 @@
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app::AppState;
+use crate::authentication::authorization::Role;
+use crate::authentication::session::get_session_user;
+use crate::authentication::AuthError;
+
+/// The admin user of the session cookie sent with the request.
+/// Add it as a handler argument to make a route admin-only.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: Role,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuthenticatedUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = state.sessions.jar(&parts.headers);
+        get_session_user(&state.pg_pool, &state.sessions, &jar)
+            .await?
+            .ok_or(AuthError::MissingCredentials)
+    }
+}
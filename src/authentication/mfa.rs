@@ -0,0 +1,380 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use totp_rs::{Algorithm, TOTP};
+use uuid::Uuid;
+
+use crate::authentication::AuthError;
+use crate::configuration::{self, MfaSettings};
+use crate::token::hash_token;
+
+const NONCE_LEN: usize = 12;
+const STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Encrypts TOTP secrets at rest and names the issuer in authenticator apps.
+#[derive(Clone)]
+pub struct MfaConfig {
+    cipher: Aes256Gcm,
+    issuer: String,
+}
+
+impl TryFrom<&MfaSettings> for MfaConfig {
+    type Error = String;
+    fn try_from(settings: &MfaSettings) -> Result<Self, Self::Error> {
+        configuration::reject_placeholder("mfa.encryption_key", &settings.encryption_key)?;
+        let key = settings.encryption_key.expose_secret().as_bytes();
+        if key.len() < 32 {
+            return Err("mfa.encryption_key must be at least 32 bytes long.".into());
+        }
+        // Any long enough string is accepted, so it is hashed down to the 256 bit AES key.
+        let cipher = Aes256Gcm::new(&Sha256::digest(key));
+        Ok(Self {
+            cipher,
+            issuer: settings.issuer.clone(),
+        })
+    }
+}
+
+impl MfaConfig {
+    // The user id is authenticated along with the secret, so a stored secret
+    // cannot be copied to another user's row.
+    fn encrypt(&self, user_id: Uuid, secret: &[u8]) -> Result<Vec<u8>, AuthError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: secret,
+            aad: user_id.as_bytes(),
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| AuthError::Unexpected("Failed to encrypt TOTP secret.".into()))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn decrypt(&self, user_id: Uuid, stored: &[u8]) -> Result<Secret<Vec<u8>>, AuthError> {
+        if stored.len() < NONCE_LEN {
+            return Err(AuthError::Unexpected(
+                "Stored TOTP secret is too short.".into(),
+            ));
+        }
+        let (nonce, ciphertext) = stored.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: user_id.as_bytes(),
+        };
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map(Secret::new)
+            .map_err(|_| AuthError::Unexpected("Failed to decrypt TOTP secret.".into()))
+    }
+
+    fn totp(&self, secret: Vec<u8>, username: &str) -> TOTP {
+        // `new_unchecked` because usernames may contain characters `new` rejects in
+        // account names; the secret is always generated by us and long enough.
+        TOTP::new_unchecked(
+            Algorithm::SHA1,
+            6,
+            0,
+            STEP_SECONDS,
+            secret,
+            Some(self.issuer.clone()),
+            username.to_string(),
+        )
+    }
+}
+
+/// What an authenticator app needs to be set up, shown once on enrolment.
+#[derive(Serialize)]
+pub struct TotpEnrolment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Generate a new TOTP secret for the user. It is not required at login until
+/// `confirm_totp_enrolment` has seen a valid code for it.
+/// Returns None if the user already has TOTP enabled.
+#[tracing::instrument(name = "Begin TOTP enrolment", skip(transaction, config))]
+pub async fn begin_totp_enrolment(
+    transaction: &mut Transaction<'_, Postgres>,
+    config: &MfaConfig,
+    user_id: Uuid,
+    username: &str,
+) -> Result<Option<TotpEnrolment>, AuthError> {
+    let secret = totp_rs::Secret::generate_secret()
+        .to_bytes()
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+    let encrypted = config.encrypt(user_id, &secret)?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE users SET totp_secret = $2, totp_last_step = NULL
+        WHERE user_id = $1 AND totp_enabled_at IS NULL
+        "#,
+        user_id,
+        encrypted,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    let totp = config.totp(secret, username);
+    Ok(Some(TotpEnrolment {
+        secret: totp.get_secret_base32(),
+        otpauth_uri: totp.get_url(),
+    }))
+}
+
+/// Enable TOTP if `code` is valid for the pending secret, replacing any recovery codes,
+/// in the transaction of the caller. Returns the new recovery codes, or None if the
+/// code is wrong or there is nothing to confirm.
+#[tracing::instrument(name = "Confirm TOTP enrolment", skip(transaction, config, code))]
+pub async fn confirm_totp_enrolment(
+    transaction: &mut Transaction<'_, Postgres>,
+    config: &MfaConfig,
+    user_id: Uuid,
+    code: &str,
+) -> Result<Option<Vec<Secret<String>>>, AuthError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username, totp_secret AS "totp_secret!" FROM users
+        WHERE user_id = $1 AND totp_secret IS NOT NULL AND totp_enabled_at IS NULL
+        FOR UPDATE
+        "#,
+        user_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let secret = config.decrypt(user_id, &row.totp_secret)?;
+    let totp = config.totp(secret.expose_secret().clone(), &row.username);
+    let Some(step) = matching_step(&totp, code, unix_time()) else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        r#"UPDATE users SET totp_enabled_at = now(), totp_last_step = $2 WHERE user_id = $1"#,
+        user_id,
+        step,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut **transaction)
+        .await?;
+    let codes: Vec<Secret<String>> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    for code in &codes {
+        sqlx::query!(
+            r#"INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)"#,
+            user_id,
+            hash_recovery_code(code.expose_secret()),
+        )
+        .execute(&mut **transaction)
+        .await?;
+    }
+    Ok(Some(codes))
+}
+
+#[tracing::instrument(name = "Check if TOTP is enabled", skip(pool))]
+pub async fn is_totp_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT totp_enabled_at IS NOT NULL AS "enabled!" FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.enabled).unwrap_or(false))
+}
+
+/// Check a TOTP code or, failing that, an unused recovery code. Both can only be used once.
+#[tracing::instrument(name = "Verify second factor", skip(pool, config, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    config: &MfaConfig,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, AuthError> {
+    let code = code.trim();
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        verify_totp_code(pool, config, user_id, code).await
+    } else {
+        use_recovery_code(pool, user_id, code).await
+    }
+}
+
+async fn verify_totp_code(
+    pool: &PgPool,
+    config: &MfaConfig,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, AuthError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username, totp_secret AS "totp_secret!" FROM users
+        WHERE user_id = $1 AND totp_secret IS NOT NULL AND totp_enabled_at IS NOT NULL
+        "#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    let secret = config.decrypt(user_id, &row.totp_secret)?;
+    let totp = config.totp(secret.expose_secret().clone(), &row.username);
+    let Some(step) = matching_step(&totp, code, unix_time()) else {
+        return Ok(false);
+    };
+    // Only accept steps after the last one used, so a seen code cannot be replayed.
+    let result = sqlx::query!(
+        r#"
+        UPDATE users SET totp_last_step = $2
+        WHERE user_id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)
+        "#,
+        user_id,
+        step,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+async fn use_recovery_code(pool: &PgPool, user_id: Uuid, code: &str) -> Result<bool, AuthError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_codes SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_recovery_code(code),
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 1 {
+        tracing::warn!("Recovery code used.");
+    }
+    Ok(result.rows_affected() == 1)
+}
+
+// The time step `code` is valid for, allowing one step of clock drift either way.
+fn matching_step(totp: &TOTP, code: &str, now: u64) -> Option<i64> {
+    [now, now.saturating_sub(STEP_SECONDS), now + STEP_SECONDS]
+        .into_iter()
+        .find(|time| totp.check(code, *time))
+        .map(|time| (time / STEP_SECONDS) as i64)
+}
+
+fn unix_time() -> u64 {
+    Utc::now().timestamp().max(0) as u64
+}
+
+// Recovery codes are typed in by hand, so case and dashes do not matter.
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_token(&normalized)
+}
+
+// Four groups of four lowercase letters and digits, about 82 bits.
+fn generate_recovery_code() -> Secret<String> {
+    let chars: Vec<char> = thread_rng()
+        .sample_iter(Alphanumeric)
+        .map(|b| char::from(b).to_ascii_lowercase())
+        .take(16)
+        .collect();
+    let groups: Vec<String> = chars.chunks(4).map(|g| g.iter().collect()).collect();
+    Secret::new(groups.join("-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_none, assert_ok, assert_some_eq};
+
+    fn config() -> MfaConfig {
+        MfaConfig::try_from(&MfaSettings {
+            encryption_key: Secret::new("an-encryption-key-that-is-long-enough".into()),
+            issuer: "zero2prod".into(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn short_encryption_keys_are_rejected() {
+        let settings = MfaSettings {
+            encryption_key: Secret::new("too-short".into()),
+            issuer: "zero2prod".into(),
+        };
+        assert!(MfaConfig::try_from(&settings).is_err());
+    }
+
+    #[test]
+    fn the_placeholder_encryption_key_is_rejected() {
+        let settings = MfaSettings {
+            encryption_key: Secret::new(
+                "replace-with-a-random-key-via-APP_MFA_ENCRYPTION_KEY_FILE".into(),
+            ),
+            issuer: "zero2prod".into(),
+        };
+        assert!(MfaConfig::try_from(&settings).is_err());
+    }
+
+    #[test]
+    fn secrets_only_decrypt_for_the_same_user() {
+        let config = config();
+        let user_id = Uuid::new_v4();
+        let stored = config.encrypt(user_id, b"a totp secret").unwrap();
+
+        assert!(!stored.windows(13).any(|w| w == b"a totp secret"));
+        let decrypted = assert_ok!(config.decrypt(user_id, &stored));
+        assert_eq!(decrypted.expose_secret(), b"a totp secret");
+        assert!(config.decrypt(Uuid::new_v4(), &stored).is_err());
+    }
+
+    #[test]
+    fn codes_are_accepted_within_one_step_of_drift() {
+        let config = config();
+        let totp = config.totp(vec![7; 20], "ursula");
+        let now = 1_700_000_000;
+        let code = totp.generate(now);
+
+        assert_some_eq!(matching_step(&totp, &code, now), (now / 30) as i64);
+        assert_some_eq!(matching_step(&totp, &code, now + 30), (now / 30) as i64);
+        assert_none!(matching_step(&totp, &code, now + 90));
+    }
+
+    #[test]
+    fn otpauth_uri_names_the_issuer_and_user() {
+        let totp = config().totp(vec![7; 20], "ursula");
+        let uri = totp.get_url();
+        assert!(uri.starts_with("otpauth://totp/zero2prod:ursula?"));
+        assert!(uri.contains(&format!("secret={}", totp.get_secret_base32())));
+    }
+
+    #[test]
+    fn recovery_codes_ignore_case_and_dashes() {
+        let code = generate_recovery_code();
+        assert_eq!(code.expose_secret().len(), 19);
+        assert_eq!(
+            hash_recovery_code(code.expose_secret()),
+            hash_recovery_code(&code.expose_secret().to_uppercase().replace('-', ""))
+        );
+        assert_ne!(
+            hash_recovery_code(code.expose_secret()),
+            hash_recovery_code(generate_recovery_code().expose_secret())
+        );
+    }
+}
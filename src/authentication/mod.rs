@@ -0,0 +1,75 @@
+pub mod api_key;
+pub use api_key::{ApiClient, Scope};
+
+pub mod authorization;
+pub use authorization::{permissions, Authorized, Permission, Role};
+
+pub mod email_change;
+
+mod extractor;
+pub use extractor::AuthenticatedUser;
+
+pub mod mfa;
+pub use mfa::MfaConfig;
+
+mod password;
+pub use password::{
+    compute_password_hash, hash_in_background, set_password, validate_credentials, Credentials,
+};
+
+pub mod password_reset;
+
+pub mod session;
+pub use session::SessionConfig;
+
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+use crate::error::Problem;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("invalid credentials")]
+    InvalidCredentials,
+    #[error("missing credentials or session expired")]
+    MissingCredentials,
+    #[error("missing API key")]
+    MissingApiKey,
+    #[error("invalid or revoked API key")]
+    InvalidApiKey,
+    #[error("the API key lacks the {} scope", .0.as_str())]
+    MissingScope(Scope),
+    #[error("the user's role does not allow {0:?}")]
+    Forbidden(Permission),
+    #[error("sqlx: {0}")]
+    PostgreSQL(#[from] sqlx::Error),
+    #[error("unexpected: {0}")]
+    Unexpected(String),
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        match self {
+            AuthError::InvalidCredentials | AuthError::MissingCredentials => {
+                tracing::warn!("Authentication failed: {}", self);
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+            AuthError::MissingApiKey | AuthError::InvalidApiKey => {
+                tracing::warn!("Authentication failed: {}", self);
+                (
+                    StatusCode::UNAUTHORIZED,
+                    [(header::WWW_AUTHENTICATE, "Bearer")],
+                )
+                    .into_response()
+            }
+            AuthError::MissingScope(_) | AuthError::Forbidden(_) => {
+                tracing::warn!("Authorization failed: {}", self);
+                Problem::new(StatusCode::FORBIDDEN, self.to_string()).into_response()
+            }
+            AuthError::PostgreSQL(_) | AuthError::Unexpected(_) => {
+                tracing::error!("Authentication error: {}", self);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use once_cell::sync::Lazy;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::authentication::AuthError;
+
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+// Verified against when the username does not exist, so unknown users take
+// as long to reject as wrong passwords and cannot be enumerated by timing.
+static DUMMY_HASH: Lazy<Secret<String>> = Lazy::new(|| {
+    compute_password_hash(Secret::new("not-a-real-password".into()))
+        .expect("Failed to hash the dummy password.")
+});
+
+/// Return the id of the user if the credentials are valid.
+#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let (user_id, expected_password_hash) =
+        match get_stored_credentials(&credentials.username, pool).await? {
+            Some((user_id, hash)) => (Some(user_id), hash),
+            None => (None, DUMMY_HASH.clone()),
+        };
+
+    // Hashing is CPU-bound and takes milliseconds: keep it off the async executor.
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| verify_password_hash(expected_password_hash, credentials.password))
+    })
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))??;
+
+    user_id.ok_or(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
+async fn get_stored_credentials(
+    username: &str,
+    pool: &PgPool,
+) -> Result<Option<(Uuid, Secret<String>)>, AuthError> {
+    let row = sqlx::query!(
+        r#"SELECT user_id, password_hash FROM users WHERE username = $1"#,
+        username,
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|row| (row.user_id, Secret::new(row.password_hash)));
+    Ok(row)
+}
+
+#[tracing::instrument(name = "Verify password hash", skip_all)]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .map_err(|_| AuthError::InvalidCredentials)
+}
+
+/// Replace the password of a user, in the transaction of the caller.
+#[tracing::instrument(name = "Set password", skip(password, transaction))]
+pub async fn set_password(
+    user_id: Uuid,
+    password: Secret<String>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), AuthError> {
+    let password_hash = hash_in_background(password).await?;
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// `compute_password_hash` off the async executor, like verification.
+pub async fn hash_in_background(password: Secret<String>) -> Result<Secret<String>, AuthError> {
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || current_span.in_scope(|| compute_password_hash(password)))
+        .await
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?
+}
+
+/// Hash a password with Argon2id, returning a PHC string to store in `users.password_hash`.
+pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, AuthError> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let params =
+        Params::new(15000, 2, 1, None).map_err(|e| AuthError::Unexpected(e.to_string()))?;
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?
+        .to_string();
+    Ok(Secret::new(password_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn hash_is_argon2id_and_salted() {
+        let first = compute_password_hash(Secret::new("correct horse".into())).unwrap();
+        let second = compute_password_hash(Secret::new("correct horse".into())).unwrap();
+        assert!(first.expose_secret().starts_with("$argon2id$"));
+        assert_ne!(first.expose_secret(), second.expose_secret());
+    }
+
+    #[test]
+    fn correct_password_is_verified() {
+        let hash = compute_password_hash(Secret::new("correct horse".into())).unwrap();
+        assert_ok!(verify_password_hash(
+            hash,
+            Secret::new("correct horse".into())
+        ));
+    }
+
+    #[test]
+    fn wrong_password_is_rejected() {
+        let hash = compute_password_hash(Secret::new("correct horse".into())).unwrap();
+        assert_err!(verify_password_hash(
+            hash,
+            Secret::new("battery staple".into())
+        ));
+    }
+}
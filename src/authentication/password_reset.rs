@@ -0,0 +1,101 @@
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::token::{generate_token, hash_token};
+
+const RESET_TOKEN_LIFETIME_MINUTES: i64 = 60;
+
+/// A reset token and the address to email it to.
+pub struct PasswordReset {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub token: Secret<String>,
+}
+
+/// Create a single-use reset token for the admin with this email, if there is one,
+/// in the transaction of the caller.
+#[tracing::instrument(name = "Create password reset token", skip(transaction, email))]
+pub async fn create_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<Option<PasswordReset>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT user_id, username, email AS "email!" FROM users WHERE lower(email) = lower($1)"#,
+        email,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let token = generate_token();
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        hash_token(&token),
+        row.user_id,
+        now,
+        now + Duration::minutes(RESET_TOKEN_LIFETIME_MINUTES),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(Some(PasswordReset {
+        user_id: row.user_id,
+        username: row.username,
+        email: row.email,
+        token: Secret::new(token),
+    }))
+}
+
+/// Set the hash of a new password with a reset token, in the transaction of the
+/// caller. The token, any other outstanding tokens of the user, and all their
+/// sessions are invalidated.
+/// Returns the id and name of the user, or None if the token is unknown, used or expired.
+#[tracing::instrument(name = "Reset password", skip(transaction, token, password_hash))]
+pub async fn reset_password(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+    password_hash: &Secret<String>,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens SET used_at = now()
+        FROM users
+        WHERE password_reset_tokens.token_hash = $1
+            AND password_reset_tokens.used_at IS NULL
+            AND password_reset_tokens.expires_at > now()
+            AND password_reset_tokens.user_id = users.user_id
+        RETURNING users.user_id, users.username
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        row.user_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL"#,
+        row.user_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(r#"DELETE FROM sessions WHERE user_id = $1"#, row.user_id)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(Some((row.user_id, row.username)))
+}
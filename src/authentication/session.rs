@@ -0,0 +1,280 @@
+use axum::http::HeaderMap;
+use axum_extra::extract::cookie::{Cookie, Key, PrivateCookieJar, SameSite};
+use base64::Engine;
+use chrono::Utc;
+use rand::Rng;
+use secrecy::ExposeSecret;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::authentication::authorization::Role;
+use crate::authentication::AuthenticatedUser;
+use crate::configuration::{self, SessionSettings};
+use crate::shutdown::CancellationToken;
+
+pub const SESSION_COOKIE: &str = "session_id";
+
+// How long the second factor can be entered after the password, and how many tries it gets.
+const MFA_PENDING_TIMEOUT_MINUTES: i64 = 5;
+const MFA_MAX_FAILED_ATTEMPTS: i32 = 5;
+
+/// How sessions are issued and when they expire.
+#[derive(Clone)]
+pub struct SessionConfig {
+    pub key: Key,
+    pub idle_timeout: chrono::Duration,
+    pub absolute_timeout: chrono::Duration,
+    pub secure_cookie: bool,
+}
+
+impl TryFrom<&SessionSettings> for SessionConfig {
+    type Error = String;
+    fn try_from(settings: &SessionSettings) -> Result<Self, Self::Error> {
+        configuration::reject_placeholder("session.key", &settings.key)?;
+        let key = settings.key.expose_secret().as_bytes();
+        if key.len() < 64 {
+            return Err("session.key must be at least 64 bytes long.".into());
+        }
+        let to_chrono = |d: Duration| {
+            chrono::Duration::from_std(d).map_err(|_| "Session timeout is too long.".to_string())
+        };
+        Ok(Self {
+            key: Key::from(key),
+            idle_timeout: to_chrono(settings.idle_timeout())?,
+            absolute_timeout: to_chrono(settings.absolute_timeout())?,
+            secure_cookie: settings.secure_cookie,
+        })
+    }
+}
+
+impl SessionConfig {
+    /// The (decrypted) cookies of a request.
+    pub fn jar(&self, headers: &HeaderMap) -> PrivateCookieJar {
+        PrivateCookieJar::from_headers(headers, self.key.clone())
+    }
+
+    /// A cookie with the same attributes as the session cookie.
+    pub fn cookie(&self, name: &'static str, value: String) -> Cookie<'static> {
+        Cookie::build(name, value)
+            .path("/")
+            .http_only(true)
+            .secure(self.secure_cookie)
+            .same_site(SameSite::Strict)
+            .finish()
+    }
+}
+
+/// Start a new session for `user_id` and add its cookie to the jar.
+/// An existing session in the jar is ended first, so the id changes on every login.
+///
+/// With `mfa_pending` the session only allows entering the second factor, see
+/// `get_mfa_pending_user`, and expires after a few minutes.
+#[tracing::instrument(name = "Start session", skip(pool, config, jar))]
+pub async fn start_session(
+    pool: &PgPool,
+    config: &SessionConfig,
+    jar: PrivateCookieJar,
+    user_id: Uuid,
+    mfa_pending: bool,
+) -> Result<PrivateCookieJar, sqlx::Error> {
+    let jar = end_session(pool, jar).await?;
+
+    let session_id = generate_session_id();
+    let now = Utc::now();
+    let expires_at = if mfa_pending {
+        now + chrono::Duration::minutes(MFA_PENDING_TIMEOUT_MINUTES).min(config.absolute_timeout)
+    } else {
+        now + config.absolute_timeout
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO sessions (session_id, user_id, created_at, last_seen_at, expires_at, mfa_pending)
+        VALUES ($1, $2, $3, $3, $4, $5)
+        "#,
+        session_id,
+        user_id,
+        now,
+        expires_at,
+        mfa_pending,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(jar.add(config.cookie(SESSION_COOKIE, session_id)))
+}
+
+/// Delete the session in the jar (if any) and remove its cookie.
+#[tracing::instrument(name = "End session", skip(pool, jar))]
+pub async fn end_session(
+    pool: &PgPool,
+    jar: PrivateCookieJar,
+) -> Result<PrivateCookieJar, sqlx::Error> {
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
+        return Ok(jar);
+    };
+    sqlx::query!(
+        r#"DELETE FROM sessions WHERE session_id = $1"#,
+        cookie.value()
+    )
+    .execute(pool)
+    .await?;
+    Ok(jar.remove(Cookie::build(SESSION_COOKIE, "").path("/").finish()))
+}
+
+/// Log the user out everywhere except in the session of the jar.
+#[tracing::instrument(name = "End other sessions", skip(transaction, jar))]
+pub async fn end_other_sessions(
+    transaction: &mut Transaction<'_, Postgres>,
+    jar: &PrivateCookieJar,
+    user_id: Uuid,
+) -> Result<u64, sqlx::Error> {
+    let current = jar.get(SESSION_COOKIE);
+    let result = sqlx::query!(
+        r#"DELETE FROM sessions WHERE user_id = $1 AND session_id IS DISTINCT FROM $2"#,
+        user_id,
+        current.as_ref().map(|c| c.value()),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Return the user of the session in the jar, if it has not timed out, and mark it as used.
+#[tracing::instrument(name = "Get session user", skip(pool, config, jar))]
+pub async fn get_session_user(
+    pool: &PgPool,
+    config: &SessionConfig,
+    jar: &PrivateCookieJar,
+) -> Result<Option<AuthenticatedUser>, sqlx::Error> {
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
+        return Ok(None);
+    };
+    let now = Utc::now();
+    let row = sqlx::query!(
+        r#"
+        UPDATE sessions SET last_seen_at = $2
+        FROM users
+        WHERE sessions.session_id = $1
+            AND sessions.user_id = users.user_id
+            AND sessions.expires_at > $2
+            AND sessions.last_seen_at > $3
+            AND NOT sessions.mfa_pending
+        RETURNING users.user_id, users.username, users.role
+        "#,
+        cookie.value(),
+        now,
+        now - config.idle_timeout,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| AuthenticatedUser {
+        user_id: r.user_id,
+        username: r.username,
+        // The column has a CHECK constraint, so only known roles can be stored.
+        role: Role::try_from(r.role.as_str()).unwrap_or(Role::Viewer),
+    }))
+}
+
+/// Return the id and name of the user of the session in the jar if it is waiting
+/// for the second factor.
+#[tracing::instrument(name = "Get MFA pending user", skip(pool, jar))]
+pub async fn get_mfa_pending_user(
+    pool: &PgPool,
+    jar: &PrivateCookieJar,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
+        return Ok(None);
+    };
+    let row = sqlx::query!(
+        r#"
+        SELECT users.user_id, users.username FROM sessions
+        JOIN users ON sessions.user_id = users.user_id
+        WHERE sessions.session_id = $1 AND sessions.mfa_pending AND sessions.expires_at > now()
+        "#,
+        cookie.value(),
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| (r.user_id, r.username)))
+}
+
+/// Count a wrong second factor for the session in the jar. After too many the
+/// session is deleted, and the password has to be entered again.
+#[tracing::instrument(name = "Record failed MFA attempt", skip(pool, jar))]
+pub async fn record_failed_mfa_attempt(
+    pool: &PgPool,
+    jar: &PrivateCookieJar,
+) -> Result<(), sqlx::Error> {
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
+        return Ok(());
+    };
+    let row = sqlx::query!(
+        r#"
+        UPDATE sessions SET mfa_failed_attempts = mfa_failed_attempts + 1
+        WHERE session_id = $1 AND mfa_pending
+        RETURNING mfa_failed_attempts
+        "#,
+        cookie.value(),
+    )
+    .fetch_optional(pool)
+    .await?;
+    if matches!(row, Some(r) if r.mfa_failed_attempts >= MFA_MAX_FAILED_ATTEMPTS) {
+        tracing::warn!("Too many wrong second factors, ending the session.");
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE session_id = $1"#,
+            cookie.value()
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Delete sessions that have passed their absolute or idle timeout.
+#[tracing::instrument(name = "Delete expired sessions", skip(pool, config))]
+pub async fn delete_expired_sessions(
+    pool: &PgPool,
+    config: &SessionConfig,
+) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let result = sqlx::query!(
+        r#"DELETE FROM sessions WHERE expires_at <= $1 OR last_seen_at <= $2"#,
+        now,
+        now - config.idle_timeout,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Periodically delete expired sessions, so the table does not grow forever.
+/// Stops when `shutdown` is cancelled, after finishing a run that is in progress.
+pub fn spawn_cleanup_task(
+    jobs: &mut JoinSet<()>,
+    pool: PgPool,
+    config: SessionConfig,
+    every: Duration,
+    shutdown: CancellationToken,
+) {
+    jobs.spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            match delete_expired_sessions(&pool, &config).await {
+                Ok(deleted) => tracing::debug!("Deleted {} expired sessions.", deleted),
+                Err(e) => tracing::error!("Failed to delete expired sessions: {:?}", e),
+            }
+        }
+    });
+}
+
+// 256 random bits, URL-safe so it can be used in a cookie as is.
+fn generate_session_id() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
@@ -0,0 +1,441 @@
+//! The `zero2prod` command line: the HTTP server, the background worker and a few
+//! admin tasks. Every command reads the configuration the same way, from `--config`.
+use clap::{Parser, Subcommand};
+use secrecy::{ExposeSecret, Secret};
+use serde::de::IntoDeserializer;
+use serde_json::json;
+use sqlx::PgPool;
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::app::{build_state, router};
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::{hash_in_background, MfaConfig, Role, SessionConfig};
+use crate::configuration::{get_configuration_from, Settings};
+use crate::models::validation::validate_password;
+use crate::models::SubscriberListQuery;
+use crate::reload::{self, RuntimeSettings};
+use crate::routes::admin::subscribers::list_page;
+use crate::shutdown::{self, CancellationToken};
+use crate::telemetry::LogWriter;
+use crate::{migrations, redact, telemetry, tls, worker};
+
+#[derive(Debug, Parser)]
+#[command(name = "zero2prod", version, about = "A newsletter service.")]
+pub struct Cli {
+    /// The directory with the configuration files, ./configuration by default.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub config: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server. This is the default command.
+    Serve {
+        /// Do not run the background jobs, because a `worker` runs them.
+        #[arg(long)]
+        no_jobs: bool,
+    },
+    /// Run the background jobs without the HTTP server.
+    Worker,
+    /// Apply the migrations that the database does not have yet.
+    Migrate,
+    /// Create an admin user. The password is read from the first line of stdin.
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        #[arg(long, default_value = "owner", value_parser = parse_role)]
+        role: Role,
+        /// Where password reset links are sent.
+        #[arg(long)]
+        email: Option<String>,
+    },
+    /// Print the subscribers as tab separated values, newest first.
+    ListSubscribers {
+        /// pending_confirmation, confirmed or unsubscribed.
+        #[arg(long)]
+        status: Option<String>,
+        /// Only subscribers whose email or name contains this.
+        #[arg(long)]
+        search: Option<String>,
+    },
+    /// Work with the configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Check that the configuration is valid, the database can be reached and is migrated.
+    Check,
+}
+
+fn parse_role(role: &str) -> Result<Role, String> {
+    Role::try_from(role)
+}
+
+/// Run the command. Errors are meant to be printed for the user.
+pub async fn run(cli: Cli) -> Result<(), String> {
+    let config_dir = cli.config.unwrap_or_else(|| {
+        std::env::current_dir()
+            .expect("Failed to determine the current directory")
+            .join("configuration")
+    });
+    let configuration = get_configuration_from(&config_dir)
+        .map_err(|e| format!("Failed to read the configuration: {}", e))?;
+    let command = cli.command.unwrap_or(Command::Serve { no_jobs: false });
+
+    // The one-off commands print their results to stdout, so they log to stderr.
+    let log_writer = match (&command, configuration.application.log_writer) {
+        (Command::Serve { .. } | Command::Worker, writer) => writer,
+        (_, LogWriter::Stdout) => LogWriter::Stderr,
+        (_, writer) => writer,
+    };
+    // Checked for every command, `config check` included, before anything is logged.
+    configuration.telemetry.check_redaction()?;
+    let tracer = telemetry::otlp_tracer(&configuration.telemetry)?;
+    redact::set_policy(
+        configuration.telemetry.redaction,
+        configuration.telemetry.redaction_salt.clone(),
+    );
+    let (subscriber, log_filter) = telemetry::get_subscriber(
+        "zero2prod".into(),
+        configuration.application.log_level.clone(),
+        configuration.application.log_format,
+        log_writer,
+        tracer,
+    );
+    telemetry::init_subscriber(subscriber, log_filter);
+
+    let result = match command {
+        Command::Serve { no_jobs } => serve(configuration, config_dir, no_jobs).await,
+        Command::Worker => run_worker(configuration, config_dir).await,
+        Command::Migrate => {
+            let pool = connect(&configuration).await?;
+            let pending = migrations::pending(&pool)
+                .await
+                .map_err(|e| format!("Failed to read the applied migrations: {}", e))?;
+            migrations::run(&pool)
+                .await
+                .map_err(|e| format!("Failed to migrate the database: {}", e))?;
+            println!("Applied {} migration(s).", pending.len());
+            Ok(())
+        }
+        Command::CreateAdmin {
+            username,
+            role,
+            email,
+        } => {
+            let pool = connect(&configuration).await?;
+            eprint!("Password: ");
+            let mut password = String::new();
+            std::io::stdin()
+                .read_line(&mut password)
+                .map_err(|e| format!("Failed to read the password: {}", e))?;
+            let password = Secret::new(password.trim_end_matches(['\r', '\n']).to_string());
+            let user_id = create_admin(&pool, &username, password, role, email.as_deref()).await?;
+            println!(
+                "Created {} {} with id {}.",
+                role.as_str(),
+                username,
+                user_id
+            );
+            Ok(())
+        }
+        Command::ListSubscribers { status, search } => {
+            let pool = connect(&configuration).await?;
+            list_subscribers(
+                &pool,
+                status.as_deref(),
+                search.as_deref(),
+                &mut std::io::stdout().lock(),
+            )
+            .await
+        }
+        Command::Config {
+            command: ConfigCommand::Check,
+        } => check_config(&configuration).await,
+    };
+    telemetry::shutdown_tracer();
+    result
+}
+
+async fn serve(configuration: Settings, config_dir: PathBuf, no_jobs: bool) -> Result<(), String> {
+    tracing::info!("config={:?}", configuration);
+    let addr: SocketAddr = format!(
+        "{}:{}",
+        configuration.application.host, configuration.application.port
+    )
+    .parse()
+    .map_err(|e| format!("Unable to parse socket address: {}", e))?;
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+    let deadline = configuration.application.shutdown_timeout();
+
+    let tls = match &configuration.application.tls {
+        Some(settings) => {
+            let config = tls::load(settings).await?;
+            tls::spawn_reloader(config.clone(), settings.clone())?;
+            Some(config)
+        }
+        None => None,
+    };
+    let redirect_listener = match configuration
+        .application
+        .tls
+        .as_ref()
+        .and_then(|settings| settings.redirect_port)
+    {
+        Some(port) => {
+            let addr = SocketAddr::new(addr.ip(), port);
+            let listener =
+                TcpListener::bind(addr).map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+            Some(listener)
+        }
+        None => None,
+    };
+
+    let shutdown = CancellationToken::new();
+    shutdown::cancel_on_signal(shutdown.clone())?;
+    let state = build_state(configuration).await?;
+    reload::spawn_watcher(state.clone(), config_dir)?;
+    // The jobs are stopped after the requests, which may still need them.
+    let jobs_shutdown = CancellationToken::new();
+    let jobs = if no_jobs {
+        JoinSet::new()
+    } else {
+        worker::spawn_jobs(&state, jobs_shutdown.clone())
+    };
+
+    let server = shutdown::serve(
+        listener,
+        router(state.clone()),
+        tls,
+        shutdown.clone(),
+        deadline,
+    );
+    let result = match redirect_listener {
+        Some(redirect_listener) => {
+            let redirect = shutdown::serve(
+                redirect_listener,
+                tls::redirect_router(addr.port()),
+                None,
+                shutdown,
+                deadline,
+            );
+            tokio::try_join!(server, redirect).map(|_| ())
+        }
+        None => server.await,
+    };
+    jobs_shutdown.cancel();
+    shutdown::finish_jobs(jobs, deadline).await;
+    state.pg_pool.close().await;
+    tracing::info!("Server stopped.");
+    result
+}
+
+async fn run_worker(configuration: Settings, config_dir: PathBuf) -> Result<(), String> {
+    tracing::info!("config={:?}", configuration);
+    let deadline = configuration.application.shutdown_timeout();
+    let shutdown = CancellationToken::new();
+    shutdown::cancel_on_signal(shutdown.clone())?;
+    let state = build_state(configuration).await?;
+    reload::spawn_watcher(state.clone(), config_dir)?;
+    let jobs = worker::spawn_jobs(&state, shutdown.clone());
+
+    shutdown.cancelled().await;
+    shutdown::finish_jobs(jobs, deadline).await;
+    state.pg_pool.close().await;
+    tracing::info!("Worker stopped.");
+    Ok(())
+}
+
+async fn connect(configuration: &Settings) -> Result<PgPool, String> {
+    configuration
+        .database
+        .pool_options()
+        .connect_with(configuration.database.with_db())
+        .await
+        .map_err(|e| format!("Failed to connect to the database: {}", e))
+}
+
+/// Store a new admin user, after checking the password like a password change does.
+pub async fn create_admin(
+    pool: &PgPool,
+    username: &str,
+    password: Secret<String>,
+    role: Role,
+    email: Option<&str>,
+) -> Result<Uuid, String> {
+    if username.trim().is_empty() {
+        return Err("The username must not be empty.".into());
+    }
+    if let Some(email) = email.filter(|email| !validator::validate_email(*email)) {
+        return Err(format!("{} is not a valid email address.", email));
+    }
+    let password = validate_password(password.expose_secret().as_str().into_deserializer())
+        .map_err(|e: serde::de::value::Error| e.to_string())?;
+    let password_hash = hash_in_background(password)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let user_id = Uuid::new_v4();
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to store the user: {}", e))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, role, email)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        role.as_str(),
+        email,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            "A user with this username or email exists already.".to_string()
+        }
+        e => format!("Failed to store the user: {}", e),
+    })?;
+
+    audit::record(
+        &mut *transaction,
+        &AuditContext::default(),
+        &Actor::system("cli"),
+        AuditEvent::new("user.create")
+            .target("user", user_id)
+            .details(json!({ "username": username, "role": role })),
+    )
+    .await
+    .map_err(|e| format!("Failed to store the user: {}", e))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| format!("Failed to store the user: {}", e))?;
+    Ok(user_id)
+}
+
+/// Write all matching subscribers to `out`, one per line: id, email, name, status and
+/// subscription time, separated by tabs.
+pub async fn list_subscribers(
+    pool: &PgPool,
+    status: Option<&str>,
+    search: Option<&str>,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let mut cursor: Option<String> = None;
+    loop {
+        // Built like the query string of the API, so it is validated the same way.
+        let query: SubscriberListQuery = serde_json::from_value(json!({
+            "status": status,
+            "q": search,
+            "cursor": cursor,
+            "limit": 200,
+        }))
+        .map_err(|e| e.to_string())?;
+        let (subscribers, next_cursor) = list_page(pool, &query)
+            .await
+            .map_err(|e| format!("Failed to list the subscribers: {}", e))?;
+        for subscriber in subscribers {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}",
+                subscriber.id,
+                subscriber.email,
+                subscriber.name,
+                subscriber.status,
+                subscriber.subscribed_at.to_rfc3339()
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        match next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return Ok(()),
+        }
+    }
+}
+
+async fn check_config(configuration: &Settings) -> Result<(), String> {
+    RuntimeSettings::try_from(configuration)?;
+    SessionConfig::try_from(&configuration.session)?;
+    MfaConfig::try_from(&configuration.mfa)?;
+    println!("The configuration is valid.");
+
+    let pool = connect(configuration).await?;
+    let pending = migrations::pending(&pool)
+        .await
+        .map_err(|e| format!("Failed to query the database: {}", e))?;
+    println!("Connected to the database.");
+    if !pending.is_empty() {
+        return Err(format!(
+            "{} migration(s) are not applied, run `zero2prod migrate`.",
+            pending.len()
+        ));
+    }
+    println!("All migrations are applied.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn the_cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn serve_is_the_default_command() {
+        let cli = Cli::try_parse_from(["zero2prod", "--config", "conf"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.config, Some(PathBuf::from("conf")));
+    }
+
+    #[test]
+    fn create_admin_needs_a_known_role() {
+        let cli = Cli::try_parse_from(["zero2prod", "create-admin", "--username", "ada"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::CreateAdmin {
+                role: Role::Owner,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from([
+            "zero2prod",
+            "create-admin",
+            "--username",
+            "ada",
+            "--role",
+            "admin"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn config_needs_a_subcommand() {
+        assert!(Cli::try_parse_from(["zero2prod", "config"]).is_err());
+        let cli = Cli::try_parse_from(["zero2prod", "config", "check"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Config {
+                command: ConfigCommand::Check
+            })
+        ));
+    }
+}
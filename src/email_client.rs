@@ -1,9 +1,16 @@
 use crate::error::Error;
+use crate::redact;
+use crate::reload::RuntimeSettings;
+use crate::telemetry;
+use arc_swap::ArcSwap;
 use reqwest::Client;
-use tracing::debug;
+use secrecy::{ExposeSecret, Secret};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, Span};
 use validator::validate_email;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValidEmail(String);
 impl ValidEmail {
     pub fn new(email: &str) -> Result<Self, String> {
@@ -19,30 +26,59 @@ impl ValidEmail {
     }
 }
 
+impl std::fmt::Debug for ValidEmail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ValidEmail")
+            .field(&redact::Email(&self.0))
+            .finish()
+    }
+}
+
+// The `provider` label of the email metrics.
+const PROVIDER: &str = "postmark";
+
 #[derive(Debug, Clone)]
 pub struct EmailClient {
     http_client: Client,
     base_url: String,
-    sender: ValidEmail,
-    authorization_token: String,
+    // The sender is read from here on every send, so a reload changes it with the rest.
+    runtime: Arc<ArcSwap<RuntimeSettings>>,
+    authorization_token: Secret<String>,
 }
 
 impl EmailClient {
     pub fn new(
         base_url: String,
-        sender: ValidEmail,
-        authorization_token: String,
+        runtime: Arc<ArcSwap<RuntimeSettings>>,
+        authorization_token: Secret<String>,
         timeout: std::time::Duration,
     ) -> Self {
         let http_client = Client::builder().timeout(timeout).build().unwrap();
         Self {
             http_client,
             base_url,
-            sender,
+            runtime,
             authorization_token,
         }
     }
 
+    pub fn sender(&self) -> ValidEmail {
+        self.runtime.load().email_sender.clone()
+    }
+
+    /// Check that the provider answers at all. Any HTTP response will do, since there
+    /// is no endpoint to check without sending an email.
+    #[tracing::instrument(name = "Checking the email provider", skip_all)]
+    pub async fn check_reachable(&self) -> Result<(), Error> {
+        self.http_client
+            .get(reqwest::Url::parse(&self.base_url)?)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    // The provider gets the `traceparent` of this span, so its side joins the trace.
+    #[tracing::instrument(name = "Sending an email", skip_all, fields(provider = PROVIDER))]
     pub async fn send_email<'a>(
         &self,
         recipient: &'a ValidEmail,
@@ -56,7 +92,7 @@ impl EmailClient {
         debug!("Creating API call to: {:?}", url);
 
         let request_body = SendEmailRequest {
-            from: self.sender.as_str().to_string(),
+            from: self.sender().as_str().to_string(),
             to: recipient.as_str().to_string(),
             subject: subject.to_string(),
             html_body: html_content.to_string(),
@@ -65,14 +101,32 @@ impl EmailClient {
 
         debug!("Request body: {:?}", url);
 
-        let _ = self
+        let labels = [("provider", PROVIDER)];
+        metrics::increment_counter!("email_send_attempts_total", &labels);
+        let mut trace_headers = reqwest::header::HeaderMap::new();
+        telemetry::inject_context(&Span::current(), &mut trace_headers);
+        let start = Instant::now();
+        let result = self
             .http_client
             .post(url)
-            .header("X-Postmark-Server-Token", &self.authorization_token)
+            .headers(trace_headers)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await
+            .and_then(|response| response.error_for_status());
+        metrics::histogram!(
+            "email_send_duration_seconds",
+            start.elapsed().as_secs_f64(),
+            &labels
+        );
+        if result.is_err() {
+            metrics::increment_counter!("email_send_failures_total", &labels);
+        }
+        result?;
         Ok(())
     }
 }
@@ -90,6 +144,7 @@ struct SendEmailRequest {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::configuration::get_configuration;
     use crate::email_client::EmailClient;
     use claim::{assert_err, assert_ok};
     use fake::faker::internet::en::SafeEmail;
@@ -126,8 +181,14 @@ mod tests {
         ValidEmail::new(&fake_sender).expect("Fake email was invalid")
     }
 
-    fn token() -> String {
-        Faker.fake()
+    fn runtime() -> Arc<ArcSwap<RuntimeSettings>> {
+        let mut runtime = RuntimeSettings::try_from(&get_configuration().unwrap()).unwrap();
+        runtime.email_sender = email();
+        Arc::new(ArcSwap::from_pointee(runtime))
+    }
+
+    fn token() -> Secret<String> {
+        Secret::new(Faker.fake())
     }
 
     fn subject() -> String {
@@ -144,7 +205,7 @@ mod tests {
         let mock_server = MockServer::start().await;
         let email_client = EmailClient::new(
             mock_server.uri(),
-            email(),
+            runtime(),
             token(),
             std::time::Duration::from_millis(200),
         );
@@ -179,8 +240,8 @@ mod tests {
         let mock_server = MockServer::start().await;
         let email_client = EmailClient::new(
             mock_server.uri(),
-            email(),
-            Faker.fake(),
+            runtime(),
+            token(),
             std::time::Duration::from_millis(200),
         );
 
@@ -204,8 +265,8 @@ mod tests {
         let mock_server = MockServer::start().await;
         let email_client = EmailClient::new(
             mock_server.uri(),
-            email(),
-            Faker.fake(),
+            runtime(),
+            token(),
             std::time::Duration::from_millis(200),
         );
 
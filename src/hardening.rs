@@ -0,0 +1,198 @@
+//! The middleware between the outside world and the handlers: body size and time
+//! limits, handler timeouts, compression, CORS for the signup endpoints and the
+//! security headers, all configured by `http` in the settings.
+use axum::body::{Body, HttpBody};
+use axum::error_handling::HandleErrorLayer;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Router};
+use std::time::Duration;
+use tower::timeout::error::Elapsed;
+use tower::ServiceBuilder;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::configuration::HttpSettings;
+use crate::error::Problem;
+
+/// `HttpSettings` with the header values parsed, so mistakes fail at startup.
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+    body_limits: BodyLimits,
+    request_timeout: Duration,
+    compression: bool,
+    cors_allowed_origins: Vec<HeaderValue>,
+    hsts: Option<HeaderValue>,
+    content_security_policy: Option<HeaderValue>,
+    referrer_policy: Option<HeaderValue>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BodyLimits {
+    max_bytes: usize,
+    timeout: Duration,
+}
+
+impl TryFrom<&HttpSettings> for HttpConfig {
+    type Error = String;
+    fn try_from(settings: &HttpSettings) -> Result<Self, Self::Error> {
+        let header = |name: &str, value: &str| {
+            HeaderValue::from_str(value).map_err(|_| format!("Invalid {}: {:?}", name, value))
+        };
+        let optional_header = |name: &str, value: &str| match value {
+            "" => Ok(None),
+            value => header(name, value).map(Some),
+        };
+        let cors_allowed_origins = settings
+            .cors_allowed_origins
+            .iter()
+            .map(|origin| header("http.cors_allowed_origins", origin))
+            .collect::<Result<_, _>>()?;
+        let hsts = match settings.hsts_max_age_seconds {
+            0 => None,
+            max_age => Some(header(
+                "http.hsts_max_age_seconds",
+                &format!("max-age={}", max_age),
+            )?),
+        };
+        Ok(Self {
+            body_limits: BodyLimits {
+                max_bytes: settings.body_limit_bytes,
+                timeout: settings.body_timeout(),
+            },
+            request_timeout: settings.request_timeout(),
+            compression: settings.compression,
+            cors_allowed_origins,
+            hsts,
+            content_security_policy: optional_header(
+                "http.content_security_policy",
+                &settings.content_security_policy,
+            )?,
+            referrer_policy: optional_header("http.referrer_policy", &settings.referrer_policy)?,
+        })
+    }
+}
+
+impl HttpConfig {
+    /// Wrap every route of `router`. Call it before the metrics and trace layers, so
+    /// the responses of these layers are measured and logged too.
+    pub fn apply<S>(&self, router: Router<S>) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let router = router
+            // Innermost: the time to read the body below does not count.
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(handle_timeout))
+                    .timeout(self.request_timeout),
+            )
+            .layer(middleware::from_fn_with_state(self.body_limits, read_body))
+            // The extractors have their own limit, keep it in line with ours.
+            .layer(DefaultBodyLimit::max(self.body_limits.max_bytes));
+
+        let mut router = router.layer(SetResponseHeaderLayer::if_not_present(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        ));
+        for (name, value) in [
+            (header::STRICT_TRANSPORT_SECURITY, &self.hsts),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                &self.content_security_policy,
+            ),
+            (header::REFERRER_POLICY, &self.referrer_policy),
+        ] {
+            if let Some(value) = value {
+                router = router.layer(SetResponseHeaderLayer::if_not_present(name, value.clone()));
+            }
+        }
+
+        if self.compression {
+            router.layer(CompressionLayer::new())
+        } else {
+            router
+        }
+    }
+
+    /// CORS for the endpoints of the signup forms embedded in other sites. Only the
+    /// allowed origins may call them, and without cookies.
+    pub fn cors(&self) -> CorsLayer {
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(self.cors_allowed_origins.clone()))
+            .allow_methods([Method::POST])
+            .allow_headers([header::CONTENT_TYPE])
+            .max_age(Duration::from_secs(60 * 60))
+    }
+}
+
+async fn handle_timeout(error: BoxError) -> Response {
+    if error.is::<Elapsed>() {
+        tracing::warn!("The request timed out.");
+        Problem::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The request took too long to process.",
+        )
+        .into_response()
+    } else {
+        tracing::error!(error = %error, "The request failed.");
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+}
+
+// Read the whole body before the handler runs, so a client that sends too much gets
+// a 413 and one that sends too slowly a 408, instead of holding a handler.
+async fn read_body(
+    State(limits): State<BodyLimits>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let too_large = || {
+        Problem::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "The request body is larger than {} bytes.",
+                limits.max_bytes
+            ),
+        )
+        .into_response()
+    };
+    let (parts, mut body) = request.into_parts();
+    if body.size_hint().lower() > limits.max_bytes as u64 {
+        return too_large();
+    }
+
+    let read = async {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(|_| {
+                Problem::new(
+                    StatusCode::BAD_REQUEST,
+                    "The request body could not be read.",
+                )
+                .into_response()
+            })?;
+            if bytes.len() + chunk.len() > limits.max_bytes {
+                return Err(too_large());
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes)
+    };
+    match tokio::time::timeout(limits.timeout, read).await {
+        Ok(Ok(bytes)) => {
+            next.run(Request::from_parts(parts, Body::from(bytes)))
+                .await
+        }
+        Ok(Err(response)) => response,
+        Err(_) => Problem::new(
+            StatusCode::REQUEST_TIMEOUT,
+            "The request body was not received in time.",
+        )
+        .into_response(),
+    }
+}
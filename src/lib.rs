@@ -1,8 +1,22 @@
 pub mod app;
+pub mod audit;
+pub mod authentication;
+pub mod cli;
 pub mod configuration;
 pub mod email_client;
 pub mod error;
+pub mod hardening;
+pub mod metrics;
+pub mod migrations;
 pub mod models;
+pub mod rate_limit;
+pub mod redact;
+pub mod reload;
+pub mod request_id;
 pub mod routes;
+pub mod shutdown;
 pub mod telemetry;
+pub mod tls;
+pub mod token;
+pub mod worker;
 pub use app::AppState;
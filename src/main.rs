@@ -1,38 +1,15 @@
-use axum::Error;
-use std;
-use std::net::SocketAddr;
+use clap::Parser;
+use std::process::ExitCode;
 
-use zero2prod::app::spawn_app;
-use zero2prod::configuration::get_configuration;
-
-mod telemetry;
+use zero2prod::cli::{self, Cli};
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
-    let subscriber = telemetry::get_subscriber("zero2prod".into(), "info".into());
-    telemetry::init_subscriber(subscriber);
-
-    // read the configuration file
-    let configuration = get_configuration().expect("Failed to read configuration.");
-
-    tracing::info!("config={:?}", configuration);
-
-    // spawn the app.
-    let app = spawn_app(configuration.clone())
-        .await
-        .expect("Failed to initialize app.");
-
-    // Serve it with hyper.
-    let addr_str = format!(
-        "{}:{}",
-        configuration.application.host, configuration.application.port
-    );
-    let addr: SocketAddr = addr_str.parse().expect("Unable to parse socket address");
-    // let addr = SocketAddr::from(([127, 0, 0, 1], configuration.application_port));
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .expect("Failed to start server.");
-
-    Ok(())
+async fn main() -> ExitCode {
+    match cli::run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
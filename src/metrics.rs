@@ -0,0 +1,151 @@
+//! Prometheus metrics, served at `/metrics` to scrapers with the bearer token of
+//! `metrics.bearer_token`. Anything recorded with the `metrics` macros ends up there;
+//! HTTP requests are recorded by `HttpMetricsLayer`.
+use axum::extract::{MatchedPath, State};
+use axum::http::{header, HeaderMap, Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+use secrecy::ExposeSecret;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+
+use crate::app::AppState;
+use crate::token::hash_token;
+
+// From 5ms to 10s, the range of both page loads and calls to the email provider.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static PROMETHEUS: OnceCell<PrometheusHandle> = OnceCell::new();
+
+/// Install the global recorder on first use, and return the handle that renders it.
+/// There is only one recorder per process, so apps in the same process share it.
+pub fn prometheus_handle() -> PrometheusHandle {
+    PROMETHEUS
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix("duration_seconds".into()), LATENCY_BUCKETS)
+                .expect("The latency buckets are not empty.")
+                .install_recorder()
+                .expect("Failed to install the Prometheus recorder.")
+        })
+        .clone()
+}
+
+/// All metrics in the Prometheus text format. 404 without a configured token, so the
+/// route does not exist for anyone until a scraper is set up.
+#[tracing::instrument(name = "Rendering metrics", skip(state, headers))]
+pub async fn metrics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    let Some(expected) = &state.metrics_token else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // Comparing hashes, so the time taken does not tell how much of the token is right.
+    if token.map(|token| hash_token(token.trim())) != Some(hash_token(expected.expose_secret())) {
+        tracing::warn!("Rejected a metrics scrape without the right bearer token.");
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response();
+    }
+
+    record_pool_metrics(&state).await;
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+        .into_response()
+}
+
+// sqlx does not report how long queries wait for a connection, so the time this
+// scrape waited for one stands in for it.
+async fn record_pool_metrics(state: &AppState) {
+    let pool = &state.pg_pool;
+    let start = Instant::now();
+    let acquired = pool.acquire().await.is_ok();
+    if acquired {
+        ::metrics::gauge!(
+            "db_pool_acquire_wait_seconds",
+            start.elapsed().as_secs_f64()
+        );
+    }
+    ::metrics::gauge!("db_pool_connections", pool.size() as f64);
+    ::metrics::gauge!("db_pool_idle_connections", pool.num_idle() as f64);
+    ::metrics::gauge!(
+        "db_pool_max_connections",
+        pool.options().get_max_connections() as f64
+    );
+}
+
+/// Counts requests and records their latency, by method, route and status.
+/// The route is the pattern, e.g. `/admin/subscribers/:subscriber_id`, so ids do not create
+/// new series; requests that match no route are counted as `unmatched`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpMetricsLayer;
+
+impl<S> Layer<S> for HttpMetricsLayer {
+    type Service = HttpMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpMetrics { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpMetrics<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for HttpMetrics<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let start = Instant::now();
+        let method = request.method().to_string();
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            let labels = [
+                ("method", method),
+                ("route", route),
+                ("status", response.status().as_u16().to_string()),
+            ];
+            ::metrics::increment_counter!("http_requests_total", &labels);
+            ::metrics::histogram!(
+                "http_request_duration_seconds",
+                start.elapsed().as_secs_f64(),
+                &labels
+            );
+            Ok(response)
+        })
+    }
+}
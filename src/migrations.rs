@@ -0,0 +1,33 @@
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::PgPool;
+
+/// The migrations in `migrations/`, embedded in the binary at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Apply the migrations that have not been applied yet.
+#[tracing::instrument(name = "Apply migrations", skip(pool))]
+pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// The versions of the embedded migrations that the database has not applied.
+#[tracing::instrument(name = "Find pending migrations", skip(pool))]
+pub async fn pending(pool: &PgPool) -> Result<Vec<i64>, sqlx::Error> {
+    // The table is created by the first `run`, without it nothing is applied.
+    let table_exists =
+        sqlx::query_scalar::<_, bool>("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(pool)
+            .await?;
+    let applied: Vec<i64> = if table_exists {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+    Ok(MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
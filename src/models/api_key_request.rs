@@ -0,0 +1,39 @@
+use serde::Deserialize;
+
+use crate::authentication::Scope;
+use crate::models::validation;
+
+#[derive(Deserialize, Debug)]
+pub struct ApiKeyRequest {
+    #[serde(deserialize_with = "validation::validate_name")]
+    name: String,
+    #[serde(deserialize_with = "validation::validate_scopes")]
+    scopes: Vec<Scope>,
+}
+
+impl ApiKeyRequest {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_scopes_are_stored_once() {
+        let request: ApiKeyRequest = serde_json::from_value(serde_json::json!({
+            "name": "CMS",
+            "scopes": ["subscribers:write", "subscribers:read", "subscribers:write"],
+        }))
+        .unwrap();
+        assert_eq!(
+            request.get_scopes(),
+            [Scope::SubscribersRead, Scope::SubscribersWrite]
+        );
+    }
+}
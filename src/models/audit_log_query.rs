@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::audit::AuditFilter;
+use crate::models::validation;
+
+/// Filters and page of `GET /admin/audit_log`. `before` is the `next_before`
+/// of the previous page.
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    actor_id: Option<Uuid>,
+    action: Option<String>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    before: Option<i64>,
+    #[serde(
+        default = "validation::default_page_limit",
+        deserialize_with = "validation::validate_page_limit"
+    )]
+    limit: i64,
+}
+
+impl AuditLogQuery {
+    pub fn get_filter(&self) -> AuditFilter<'_> {
+        AuditFilter {
+            actor_id: self.actor_id,
+            action: self.action.as_deref(),
+            target_type: self.target_type.as_deref(),
+            target_id: self.target_id.as_deref(),
+            since: self.since,
+            until: self.until,
+        }
+    }
+    pub fn get_before(&self) -> Option<i64> {
+        self.before
+    }
+    pub fn get_limit(&self) -> i64 {
+        self.limit
+    }
+}
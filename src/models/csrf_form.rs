@@ -0,0 +1,46 @@
+use serde::Deserialize;
+
+/// A form of the HTML pages: its fields, and the CSRF token in a hidden field.
+/// Forms with only a button use `CsrfForm<()>`.
+#[derive(Deserialize, Debug)]
+pub struct CsrfForm<T> {
+    csrf_token: String,
+    #[serde(flatten)]
+    payload: T,
+}
+
+impl<T> CsrfForm<T> {
+    pub fn get_csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+    pub fn get_payload(&self) -> &T {
+        &self.payload
+    }
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SubscriberUpdateRequest;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn the_token_is_required() {
+        assert_err!(serde_urlencoded::from_str::<CsrfForm<()>>(""));
+        let form: CsrfForm<()> = serde_urlencoded::from_str("csrf_token=abc").unwrap();
+        assert_eq!(form.get_csrf_token(), "abc");
+    }
+
+    #[test]
+    fn the_fields_are_validated() {
+        assert_ok!(serde_urlencoded::from_str::<
+            CsrfForm<SubscriberUpdateRequest>,
+        >("csrf_token=abc&name=Ursula"));
+        assert_err!(serde_urlencoded::from_str::<
+            CsrfForm<SubscriberUpdateRequest>,
+        >("csrf_token=abc&name=+"));
+    }
+}
@@ -0,0 +1,43 @@
+use secrecy::Secret;
+use serde::Deserialize;
+use std::fmt;
+
+use crate::models::validation;
+use crate::redact;
+
+#[derive(Deserialize)]
+pub struct EmailRequest {
+    #[serde(deserialize_with = "validation::validate_email_address")]
+    email: String,
+}
+
+impl EmailRequest {
+    pub fn get_email(&self) -> &str {
+        &self.email
+    }
+}
+
+impl fmt::Debug for EmailRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmailRequest")
+            .field("email", &redact::Email(&self.email))
+            .finish()
+    }
+}
+
+/// A new address for the logged in admin, with their password.
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    current_password: Secret<String>,
+    #[serde(deserialize_with = "validation::validate_email_address")]
+    email: String,
+}
+
+impl ChangeEmailRequest {
+    pub fn get_current_password(&self) -> &Secret<String> {
+        &self.current_password
+    }
+    pub fn get_email(&self) -> &str {
+        &self.email
+    }
+}
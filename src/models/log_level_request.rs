@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+/// An `EnvFilter` directive, e.g. `debug` or `info,sqlx=warn`.
+#[derive(Deserialize, Debug)]
+pub struct LogLevelRequest {
+    directive: String,
+}
+
+impl LogLevelRequest {
+    pub fn get_directive(&self) -> &str {
+        &self.directive
+    }
+}
@@ -0,0 +1,23 @@
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::authentication::Credentials;
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    password: Secret<String>,
+}
+
+impl LoginRequest {
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn into_credentials(self) -> Credentials {
+        Credentials {
+            username: self.username,
+            password: self.password,
+        }
+    }
+}
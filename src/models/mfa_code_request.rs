@@ -0,0 +1,16 @@
+use serde::Deserialize;
+
+use crate::models::validation;
+
+/// A TOTP code from an authenticator app, or a recovery code.
+#[derive(Deserialize)]
+pub struct MfaCodeRequest {
+    #[serde(deserialize_with = "validation::validate_not_empty")]
+    code: String,
+}
+
+impl MfaCodeRequest {
+    pub fn get_code(&self) -> &str {
+        &self.code
+    }
+}
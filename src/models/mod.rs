@@ -1,6 +1,39 @@
+mod api_key_request;
+pub use api_key_request::ApiKeyRequest;
+
+mod audit_log_query;
+pub use audit_log_query::AuditLogQuery;
+
+mod csrf_form;
+pub use csrf_form::CsrfForm;
+
+mod email_request;
+pub use email_request::{ChangeEmailRequest, EmailRequest};
+
+mod log_level_request;
+pub use log_level_request::LogLevelRequest;
+
+mod login_request;
+pub use login_request::LoginRequest;
+
+mod mfa_code_request;
+pub use mfa_code_request::MfaCodeRequest;
+
 mod new_subscriber;
 pub use new_subscriber::NewSubscriber;
 
+mod password_request;
+pub use password_request::{ChangePasswordRequest, ResetPasswordRequest};
+
+mod role_request;
+pub use role_request::RoleRequest;
+
+mod subscriber_list_query;
+pub use subscriber_list_query::{SortOrder, SubscriberListQuery, SubscriberSort};
+
+mod subscriber_update_request;
+pub use subscriber_update_request::SubscriberUpdateRequest;
+
 mod token_query;
 pub use token_query::TokenQuery;
 
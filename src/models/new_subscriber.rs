@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 use crate::models::validation;
+use crate::redact;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize)]
 pub struct NewSubscriber {
     #[serde(deserialize_with = "validation::validate_email_address")]
     email: String,
@@ -23,6 +25,15 @@ impl NewSubscriber {
     }
 }
 
+impl fmt::Debug for NewSubscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NewSubscriber")
+            .field("email", &redact::Email(&self.email))
+            .field("name", &redact::Name(&self.name))
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -60,9 +71,17 @@ mod tests {
         };
         let json_str = serde_json::to_string(&body).expect("Failed so serialize request.");
         let parsed_data = serde_json::from_str::<NewSubscriber>(&json_str);
-        match parsed_data {
-            Ok(_) => return true,
-            Err(_) => return false,
-        };
+        parsed_data.is_ok()
+    }
+
+    #[test]
+    fn debug_output_does_not_contain_the_email_or_name() {
+        let subscriber: NewSubscriber =
+            serde_json::from_str(r#"{ "email": "ursula@example.com", "name": "Ursula le Guin" }"#)
+                .unwrap();
+        let debug = format!("{:?}", subscriber);
+        assert!(debug.starts_with("NewSubscriber"));
+        assert!(!debug.contains("ursula@example.com"));
+        assert!(!debug.contains("Ursula le Guin"));
     }
 }
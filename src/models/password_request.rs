@@ -0,0 +1,75 @@
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::models::validation;
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    current_password: Secret<String>,
+    #[serde(deserialize_with = "validation::validate_password")]
+    new_password: Secret<String>,
+}
+
+impl ChangePasswordRequest {
+    pub fn get_current_password(&self) -> &Secret<String> {
+        &self.current_password
+    }
+    pub fn get_new_password(&self) -> &Secret<String> {
+        &self.new_password
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    #[serde(deserialize_with = "validation::validate_not_empty")]
+    token: String,
+    #[serde(deserialize_with = "validation::validate_password")]
+    new_password: Secret<String>,
+}
+
+impl ResetPasswordRequest {
+    pub fn get_token(&self) -> &str {
+        &self.token
+    }
+    pub fn get_new_password(&self) -> &Secret<String> {
+        &self.new_password
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    fn parse(new_password: &str) -> Result<ChangePasswordRequest, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "current_password": "old password",
+            "new_password": new_password,
+        }))
+    }
+
+    #[test]
+    fn strong_passwords_are_accepted() {
+        let request = parse("correct horse battery staple 7").unwrap();
+        assert_eq!(
+            request.get_new_password().expose_secret(),
+            "correct horse battery staple 7"
+        );
+    }
+
+    #[test]
+    fn short_passwords_are_rejected() {
+        assert!(parse("sh0rt!").is_err());
+    }
+
+    #[test]
+    fn very_long_passwords_are_rejected() {
+        assert!(parse(&format!("{}1", "a".repeat(128))).is_err());
+    }
+
+    #[test]
+    fn passwords_need_letters_and_something_else() {
+        assert!(parse("onlylettersinhere").is_err());
+        assert!(parse("1234567890123").is_err());
+    }
+}
@@ -0,0 +1,14 @@
+use serde::Deserialize;
+
+use crate::authentication::Role;
+
+#[derive(Deserialize, Debug)]
+pub struct RoleRequest {
+    role: Role,
+}
+
+impl RoleRequest {
+    pub fn get_role(&self) -> Role {
+        self.role
+    }
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fmt;
+
+use crate::models::validation;
+use crate::redact;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberSort {
+    #[default]
+    SubscribedAt,
+    Email,
+    Name,
+}
+
+impl SubscriberSort {
+    pub fn column(&self) -> &'static str {
+        match self {
+            SubscriberSort::SubscribedAt => "subscribed_at",
+            SubscriberSort::Email => "email",
+            SubscriberSort::Name => "name",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Filters, sort and page of `GET /admin/subscribers`. `cursor` is the `next_cursor`
+/// of the previous page, and only valid with the same sort and order.
+#[derive(Deserialize)]
+pub struct SubscriberListQuery {
+    #[serde(default, deserialize_with = "validation::validate_subscription_status")]
+    status: Option<String>,
+    subscribed_from: Option<DateTime<Utc>>,
+    subscribed_to: Option<DateTime<Utc>>,
+    /// Case-insensitive substring of the email or name.
+    q: Option<String>,
+    #[serde(default)]
+    sort: SubscriberSort,
+    #[serde(default)]
+    order: SortOrder,
+    cursor: Option<String>,
+    #[serde(
+        default = "validation::default_page_limit",
+        deserialize_with = "validation::validate_page_limit"
+    )]
+    limit: i64,
+}
+
+// The search term and the cursor (sorted by email, it holds one) can be personal data.
+impl fmt::Debug for SubscriberListQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscriberListQuery")
+            .field("status", &self.status)
+            .field("subscribed_from", &self.subscribed_from)
+            .field("subscribed_to", &self.subscribed_to)
+            .field("q", &self.q.as_deref().map(redact::Name))
+            .field("sort", &self.sort)
+            .field("order", &self.order)
+            .field("cursor", &self.cursor.as_deref().map(redact::Token))
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+// No filters, the first page.
+impl Default for SubscriberListQuery {
+    fn default() -> Self {
+        Self {
+            status: None,
+            subscribed_from: None,
+            subscribed_to: None,
+            q: None,
+            sort: SubscriberSort::default(),
+            order: SortOrder::default(),
+            cursor: None,
+            limit: validation::default_page_limit(),
+        }
+    }
+}
+
+impl SubscriberListQuery {
+    pub fn get_status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+    pub fn get_subscribed_from(&self) -> Option<DateTime<Utc>> {
+        self.subscribed_from
+    }
+    pub fn get_subscribed_to(&self) -> Option<DateTime<Utc>> {
+        self.subscribed_to
+    }
+    pub fn get_search(&self) -> Option<&str> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
+    }
+    pub fn get_sort(&self) -> SubscriberSort {
+        self.sort
+    }
+    pub fn get_order(&self) -> SortOrder {
+        self.order
+    }
+    pub fn get_cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+    pub fn get_limit(&self) -> i64 {
+        self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Result<SubscriberListQuery, serde_urlencoded::de::Error> {
+        serde_urlencoded::from_str(query)
+    }
+
+    #[test]
+    fn defaults_to_newest_first() {
+        let query = parse("").unwrap();
+        assert_eq!(query.get_sort(), SubscriberSort::SubscribedAt);
+        assert_eq!(query.get_order(), SortOrder::Desc);
+        assert_eq!(query.get_limit(), 50);
+        assert_eq!(query.get_search(), None);
+    }
+
+    #[test]
+    fn unknown_statuses_and_sorts_are_rejected() {
+        assert!(parse("status=deleted").is_err());
+        assert!(parse("sort=id").is_err());
+        assert!(parse("limit=0").is_err());
+        assert!(parse("status=confirmed&sort=email&order=asc&limit=200").is_ok());
+    }
+
+    #[test]
+    fn blank_searches_and_statuses_are_ignored() {
+        assert_eq!(parse("status=").unwrap().get_status(), None);
+        assert_eq!(parse("q=%20%20").unwrap().get_search(), None);
+        assert_eq!(parse("q=%20ursula").unwrap().get_search(), Some("ursula"));
+    }
+}
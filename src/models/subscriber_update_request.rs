@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use std::fmt;
+
+use crate::models::validation;
+use crate::redact;
+
+#[derive(Deserialize)]
+pub struct SubscriberUpdateRequest {
+    #[serde(deserialize_with = "validation::validate_name")]
+    name: String,
+}
+
+impl SubscriberUpdateRequest {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Debug for SubscriberUpdateRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscriberUpdateRequest")
+            .field("name", &redact::Name(&self.name))
+            .finish()
+    }
+}
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::redact;
+
+#[derive(Deserialize, Serialize)]
 pub struct TokenQuery {
     token: String,
 }
@@ -14,3 +17,11 @@ impl TokenQuery {
     //     format("token={}", &self.token)
     // }
 }
+
+impl fmt::Debug for TokenQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenQuery")
+            .field("token", &redact::Token(&self.token))
+            .finish()
+    }
+}
@@ -1,3 +1,5 @@
+use crate::authentication::Scope;
+use secrecy::Secret;
 use serde::{Deserialize, Deserializer};
 use unicode_segmentation::UnicodeSegmentation;
 use validator::validate_email;
@@ -9,10 +11,10 @@ where
     let s: String = String::deserialize(deserializer)?;
     let is_valid = validate_email(&s);
     if is_valid {
-        return Ok(s);
+        Ok(s)
     } else {
-        return Err(serde::de::Error::custom("Not a valid email address."));
-    };
+        Err(serde::de::Error::custom("Not a valid email address."))
+    }
 }
 
 pub fn validate_name<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -46,3 +48,88 @@ where
     }
     Ok(s)
 }
+
+pub fn validate_not_empty<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = String::deserialize(deserializer)?;
+    if s.trim().is_empty() {
+        return Err(serde::de::Error::custom("Value is empty string."));
+    }
+    Ok(s)
+}
+
+pub fn validate_scopes<'de, D>(deserializer: D) -> Result<Vec<Scope>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut scopes: Vec<Scope> = Vec::deserialize(deserializer)?;
+    if scopes.is_empty() {
+        return Err(serde::de::Error::custom("At least one scope is required."));
+    }
+    // `dedup` only removes repeats next to each other.
+    scopes.sort();
+    scopes.dedup();
+    Ok(scopes)
+}
+
+/// 12 to 128 characters, with letters and at least one digit or symbol.
+/// The upper limit keeps hashing cheap enough.
+pub fn validate_password<'de, D>(deserializer: D) -> Result<Secret<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = String::deserialize(deserializer)?;
+    let length = s.chars().count();
+    if length < 12 {
+        return Err(serde::de::Error::custom(
+            "Password must be at least 12 characters long.",
+        ));
+    }
+    if length > 128 {
+        return Err(serde::de::Error::custom(
+            "Password must be at most 128 characters long.",
+        ));
+    }
+    if !s.chars().any(char::is_alphabetic) || s.chars().all(char::is_alphabetic) {
+        return Err(serde::de::Error::custom(
+            "Password must contain letters and at least one digit or symbol.",
+        ));
+    }
+    Ok(Secret::new(s))
+}
+
+/// Page sizes for paginated admin listings.
+pub fn validate_page_limit<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let limit = i64::deserialize(deserializer)?;
+    if !(1..=200).contains(&limit) {
+        return Err(serde::de::Error::custom("limit must be between 1 and 200."));
+    }
+    Ok(limit)
+}
+
+pub fn default_page_limit() -> i64 {
+    50
+}
+
+/// The values of `subscriptions.status`.
+pub const SUBSCRIPTION_STATUSES: &[&str] = &["pending_confirmation", "confirmed", "unsubscribed"];
+
+pub fn validate_subscription_status<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let status: Option<String> = Option::deserialize(deserializer)?;
+    match status {
+        // What a form sends for "any status".
+        Some(s) if s.is_empty() => Ok(None),
+        Some(s) if !SUBSCRIPTION_STATUSES.contains(&s.as_str()) => Err(serde::de::Error::custom(
+            format!("{} is not a known subscription status.", s),
+        )),
+        status => Ok(status),
+    }
+}
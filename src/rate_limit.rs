@@ -0,0 +1,428 @@
+//! Limits on the endpoints anyone can call: `/subscribe` sends an email to any
+//! address it is given, and `/confirm` could be used to guess tokens. Requests are
+//! counted per client IP and, for `/subscribe`, per email, in a window that starts
+//! with the first request. Requests over a limit get a 429 with `Retry-After`.
+//!
+//! The limits are runtime settings, so they can be changed with a reload. With the
+//! `postgres` backend the counts are shared by all instances.
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, FromRequest, State};
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use ipnet::{IpNet, Ipv6Net};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+use crate::app::AppState;
+use crate::configuration::RateLimitSettings;
+use crate::error::Problem;
+use crate::shutdown::CancellationToken;
+use crate::token::hash_token;
+
+/// Where the requests are counted.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitBackend {
+    /// In the process: every instance has its own counts, which a restart resets.
+    #[default]
+    Memory,
+    /// In the `rate_limits` table, for all instances.
+    Postgres,
+}
+
+// Expired windows of the memory backend are dropped at most this often.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The counts of the `memory` backend. Clones share them.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    memory: Arc<Mutex<MemoryStore>>,
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    windows: HashMap<String, Window>,
+    pruned_at: Option<Instant>,
+}
+
+struct Window {
+    hits: u32,
+    expires_at: Instant,
+}
+
+impl RateLimiter {
+    /// Count a request for `key`. Returns how many requests were made in the current
+    /// window, this one included, and when the window ends.
+    pub async fn hit(
+        &self,
+        pool: &PgPool,
+        settings: &RateLimitSettings,
+        key: &str,
+    ) -> Result<(u32, Duration), sqlx::Error> {
+        match settings.backend {
+            RateLimitBackend::Memory => Ok(self.hit_memory(key, settings.window())),
+            RateLimitBackend::Postgres => hit_postgres(pool, key, settings.window()).await,
+        }
+    }
+
+    fn hit_memory(&self, key: &str, window: Duration) -> (u32, Duration) {
+        let now = Instant::now();
+        let mut store = self.memory.lock().unwrap();
+        if store
+            .pruned_at
+            .is_none_or(|at| now.duration_since(at) >= PRUNE_INTERVAL)
+        {
+            store.windows.retain(|_, w| w.expires_at > now);
+            store.pruned_at = Some(now);
+        }
+
+        let new_window = || Window {
+            hits: 0,
+            expires_at: now + window,
+        };
+        let current = store
+            .windows
+            .entry(key.to_string())
+            .and_modify(|w| {
+                if w.expires_at <= now {
+                    *w = new_window();
+                }
+            })
+            .or_insert_with(new_window);
+        current.hits += 1;
+        (current.hits, current.expires_at - now)
+    }
+}
+
+#[tracing::instrument(name = "Count request for rate limit", skip(pool, key))]
+async fn hit_postgres(
+    pool: &PgPool,
+    key: &str,
+    window: Duration,
+) -> Result<(u32, Duration), sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO rate_limits (key, hits, expires_at)
+        VALUES ($1, 1, now() + make_interval(secs => $2))
+        ON CONFLICT (key) DO UPDATE SET
+            hits = CASE WHEN rate_limits.expires_at <= now() THEN 1
+                ELSE rate_limits.hits + 1 END,
+            expires_at = CASE WHEN rate_limits.expires_at <= now() THEN EXCLUDED.expires_at
+                ELSE rate_limits.expires_at END
+        RETURNING hits, expires_at
+        "#,
+        key,
+        window.as_secs_f64(),
+    )
+    .fetch_one(pool)
+    .await?;
+    let remaining = (row.expires_at - Utc::now()).to_std().unwrap_or_default();
+    Ok((row.hits.max(0) as u32, remaining))
+}
+
+/// Delete the windows that have ended from the `rate_limits` table.
+pub async fn delete_expired(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(r#"DELETE FROM rate_limits WHERE expires_at < now()"#)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Periodically delete ended windows, so the table does not grow forever.
+/// Stops when `shutdown` is cancelled, after finishing a run that is in progress.
+pub fn spawn_cleanup_task(
+    jobs: &mut JoinSet<()>,
+    pool: PgPool,
+    every: Duration,
+    shutdown: CancellationToken,
+) {
+    jobs.spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            match delete_expired(&pool).await {
+                Ok(deleted) => tracing::debug!("Deleted {} expired rate limits.", deleted),
+                Err(e) => tracing::error!("Failed to delete expired rate limits: {:?}", e),
+            }
+        }
+    });
+}
+
+/// The address of the client: the peer, or if the peer is a trusted proxy, the last
+/// address in `X-Forwarded-For` that is not one. Unknown without `ConnectInfo`.
+pub fn client_ip(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let mut client = peer?;
+    let forwarded: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    // Every proxy appends the address it got the request from, so only the end of
+    // the list, added by our own proxies, can be believed.
+    for hop in forwarded.into_iter().rev() {
+        if !trusted_proxies.iter().any(|proxy| proxy.contains(&client)) {
+            break;
+        }
+        match hop.parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+    Some(client)
+}
+
+// IPv6 clients usually get a whole /64, so they are counted per prefix: one address
+// each would let them pick a fresh one for every request.
+fn ip_key(endpoint: &str, ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => format!("{}:ip:{}", endpoint, ip),
+        IpAddr::V6(ip) => {
+            let prefix = Ipv6Net::new(ip, 64).expect("64 is a valid prefix length.");
+            format!("{}:ip:{}", endpoint, prefix.trunc())
+        }
+    }
+}
+
+// The rate limit keys of emails are hashed, so the table holds no addresses.
+fn email_key(email: &str) -> String {
+    format!(
+        "subscribe:email:{}",
+        hash_token(&email.trim().to_lowercase())
+    )
+}
+
+// Count the request and answer 429 if it is over `limit`. Requests are let through
+// if they cannot be counted, an unavailable database already fails them anyway.
+async fn check(
+    state: &AppState,
+    settings: &RateLimitSettings,
+    endpoint: &'static str,
+    scope: &'static str,
+    key: &str,
+    limit: u32,
+) -> Result<(), Response> {
+    if limit == 0 {
+        return Ok(());
+    }
+    let (hits, retry_after) = match state.rate_limiter.hit(&state.pg_pool, settings, key).await {
+        Ok(hit) => hit,
+        Err(e) => {
+            tracing::error!("Failed to count the request for the rate limit: {:?}", e);
+            return Ok(());
+        }
+    };
+    if hits <= limit {
+        return Ok(());
+    }
+
+    tracing::warn!(endpoint, scope, "Rate limit exceeded.");
+    metrics::increment_counter!("rate_limited_requests_total", "endpoint" => endpoint, "scope" => scope);
+    // Whole seconds, rounded up so the client does not come back too early.
+    let seconds = (retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)).max(1);
+    let mut response = Problem::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("Too many requests, try again in {} seconds.", seconds),
+    )
+    .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+    Err(response)
+}
+
+#[derive(serde::Deserialize)]
+struct SubscribeBody {
+    email: String,
+}
+
+/// Middleware for `/subscribe`: limits per client IP and per email.
+pub async fn limit_subscribe(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let runtime = state.runtime.load_full();
+    let settings = &runtime.rate_limits;
+    if !settings.enabled {
+        return next.run(request).await;
+    }
+
+    let peer = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    if let Some(ip) = client_ip(peer, request.headers(), &settings.trusted_proxies) {
+        let key = ip_key("subscribe", ip);
+        if let Err(response) = check(
+            &state,
+            settings,
+            "subscribe",
+            "ip",
+            &key,
+            settings.subscribe_per_ip,
+        )
+        .await
+        {
+            return response;
+        }
+    }
+
+    // The email is in the body, which the handler needs again afterwards. A body
+    // without one is left to the handler to reject.
+    let (parts, body) = request.into_parts();
+    let bytes = match Bytes::from_request(Request::new(body), &()).await {
+        Ok(bytes) => bytes,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if let Ok(SubscribeBody { email }) = serde_json::from_slice(&bytes) {
+        let key = email_key(&email);
+        if let Err(response) = check(
+            &state,
+            settings,
+            "subscribe",
+            "email",
+            &key,
+            settings.subscribe_per_email,
+        )
+        .await
+        {
+            return response;
+        }
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+/// Middleware for `/confirm`: limits per client IP.
+pub async fn limit_confirm(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let runtime = state.runtime.load_full();
+    let settings = &runtime.rate_limits;
+    if !settings.enabled {
+        return next.run(request).await;
+    }
+
+    let peer = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    if let Some(ip) = client_ip(peer, request.headers(), &settings.trusted_proxies) {
+        let key = ip_key("confirm", ip);
+        if let Err(response) = check(
+            &state,
+            settings,
+            "confirm",
+            "ip",
+            &key,
+            settings.confirm_per_ip,
+        )
+        .await
+        {
+            return response;
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let headers = forwarded_for("203.0.113.7");
+        assert_eq!(
+            client_ip(ip("198.51.100.1"), &headers, &[]),
+            ip("198.51.100.1")
+        );
+
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+        assert_eq!(
+            client_ip(ip("198.51.100.1"), &headers, &trusted),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn forwarded_for_is_followed_through_trusted_proxies_only() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+
+        let headers = forwarded_for("203.0.113.7");
+        assert_eq!(
+            client_ip(ip("10.0.0.2"), &headers, &trusted),
+            ip("203.0.113.7")
+        );
+
+        // The client made up the first address, our proxies added the others.
+        let headers = forwarded_for("1.2.3.4, 203.0.113.7, 10.0.0.3");
+        assert_eq!(
+            client_ip(ip("10.0.0.2"), &headers, &trusted),
+            ip("203.0.113.7")
+        );
+
+        // Nothing is known before an address that cannot be parsed.
+        let headers = forwarded_for("203.0.113.7, garbage");
+        assert_eq!(
+            client_ip(ip("10.0.0.2"), &headers, &trusted),
+            ip("10.0.0.2")
+        );
+
+        assert_eq!(client_ip(None, &headers, &trusted), None);
+    }
+
+    #[test]
+    fn memory_windows_count_hits_until_they_end() {
+        let limiter = RateLimiter::default();
+        let window = Duration::from_millis(50);
+
+        assert_eq!(limiter.hit_memory("a", window).0, 1);
+        assert_eq!(limiter.hit_memory("a", window).0, 2);
+        assert_eq!(limiter.hit_memory("b", window).0, 1);
+        let (_, remaining) = limiter.hit_memory("a", window);
+        assert!(remaining <= window);
+
+        std::thread::sleep(window);
+        assert_eq!(limiter.hit_memory("a", window).0, 1);
+    }
+
+    #[test]
+    fn ipv6_clients_are_counted_per_64_prefix() {
+        let key = |ip: &str| ip_key("subscribe", ip.parse().unwrap());
+        assert_eq!(key("2001:db8:1:2::1"), "subscribe:ip:2001:db8:1:2::/64");
+        assert_eq!(key("2001:db8:1:2::1"), key("2001:db8:1:2:ffff::9"));
+        assert_ne!(key("2001:db8:1:2::1"), key("2001:db8:1:3::1"));
+        assert_eq!(key("203.0.113.1"), "subscribe:ip:203.0.113.1");
+        assert_eq!(key("::ffff:203.0.113.1"), key("203.0.113.1"));
+    }
+
+    #[test]
+    fn email_keys_ignore_case_and_hide_the_address() {
+        let key = email_key("Ada@Example.com");
+        assert_eq!(key, email_key("ada@example.com "));
+        assert!(!key.contains("ada"));
+    }
+}
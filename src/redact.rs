@@ -0,0 +1,187 @@
+//! Personal data in logs and spans: wrap it in `Email`, `Name` or `Token` and it is
+//! written according to the redaction policy of the configuration, e.g.
+//! `tracing::info!(subscriber_email = %redact::Email(email))`. Database errors go
+//! through `DbError`, which leaves out the values Postgres quotes.
+use arc_swap::ArcSwap;
+use once_cell::sync::Lazy;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Arc;
+
+/// How personal data is written to logs and spans.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionPolicy {
+    /// A salted SHA-256 prefix: the same value gives the same hash, so a subscriber
+    /// can be followed through the logs without being named.
+    #[default]
+    Hash,
+    /// The first character and, for emails, the domain: `a***@example.com`.
+    Mask,
+    /// Nothing but `[redacted]`.
+    Drop,
+}
+
+#[derive(Debug)]
+struct Redaction {
+    policy: RedactionPolicy,
+    salt: Secret<String>,
+}
+
+static REDACTION: Lazy<ArcSwap<Redaction>> = Lazy::new(|| {
+    ArcSwap::from_pointee(Redaction {
+        policy: RedactionPolicy::default(),
+        salt: Secret::new(String::new()),
+    })
+});
+
+/// Apply `policy` to everything logged from now on. The salt keeps hashes of emails
+/// from being reversed by hashing a list of known addresses.
+pub fn set_policy(policy: RedactionPolicy, salt: Secret<String>) {
+    REDACTION.store(Arc::new(Redaction { policy, salt }));
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Email,
+    Name,
+    Token,
+}
+
+fn redact(value: &str, kind: Kind, redaction: &Redaction) -> String {
+    match redaction.policy {
+        RedactionPolicy::Hash => {
+            let mut hasher = Sha256::new();
+            hasher.update(redaction.salt.expose_secret().as_bytes());
+            hasher.update(value.as_bytes());
+            let hash: String = hasher.finalize()[..8]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            format!("sha256:{}", hash)
+        }
+        RedactionPolicy::Mask => {
+            let first: String = value.chars().take(1).collect();
+            match (kind, value.rsplit_once('@')) {
+                (Kind::Email, Some((_, domain))) => format!("{}***@{}", first, domain),
+                _ => format!("{}***", first),
+            }
+        }
+        RedactionPolicy::Drop => "[redacted]".into(),
+    }
+}
+
+macro_rules! redacted {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy)]
+        pub struct $name<'a>(pub &'a str);
+
+        impl fmt::Display for $name<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&redact(self.0, Kind::$name, &REDACTION.load()))
+            }
+        }
+
+        impl fmt::Debug for $name<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(self, f)
+            }
+        }
+    };
+}
+
+redacted!(
+    /// An email address of a subscriber or user.
+    Email
+);
+redacted!(
+    /// The name of a subscriber.
+    Name
+);
+redacted!(
+    /// A subscription, password reset or similar token.
+    Token
+);
+
+/// A database error as it can be logged. Postgres puts values into the message and
+/// detail of its errors, e.g. `Key (email)=(ada@example.com) already exists.`, so
+/// only the SQLSTATE and the constraint of those are written.
+pub struct DbError<'a>(pub &'a sqlx::Error);
+
+impl fmt::Display for DbError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            sqlx::Error::Database(e) => {
+                write!(f, "database error {}", e.code().unwrap_or_default())?;
+                if let Some(constraint) = e.constraint() {
+                    write!(f, " on constraint {}", constraint)?;
+                }
+                Ok(())
+            }
+            e => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl fmt::Debug for DbError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redaction(policy: RedactionPolicy, salt: &str) -> Redaction {
+        Redaction {
+            policy,
+            salt: Secret::new(salt.into()),
+        }
+    }
+
+    #[test]
+    fn hashes_are_stable_and_depend_on_the_salt() {
+        let first = redaction(RedactionPolicy::Hash, "pepper");
+        let hash = redact("ada@example.com", Kind::Email, &first);
+        assert!(hash.starts_with("sha256:"));
+        assert_eq!(hash.len(), "sha256:".len() + 16);
+        assert!(!hash.contains("ada"));
+        assert_eq!(hash, redact("ada@example.com", Kind::Email, &first));
+        assert_ne!(hash, redact("bob@example.com", Kind::Email, &first));
+
+        let other_salt = redaction(RedactionPolicy::Hash, "salt");
+        assert_ne!(hash, redact("ada@example.com", Kind::Email, &other_salt));
+    }
+
+    #[test]
+    fn masks_keep_the_first_character_and_the_domain_of_emails() {
+        let mask = redaction(RedactionPolicy::Mask, "");
+        assert_eq!(
+            redact("ada@example.com", Kind::Email, &mask),
+            "a***@example.com"
+        );
+        assert_eq!(redact("not-an-email", Kind::Email, &mask), "n***");
+        assert_eq!(redact("Ada Lovelace", Kind::Name, &mask), "A***");
+        assert_eq!(redact("Ümit", Kind::Name, &mask), "Ü***");
+        assert_eq!(redact("abcdef", Kind::Token, &mask), "a***");
+        assert_eq!(redact("", Kind::Name, &mask), "***");
+    }
+
+    #[test]
+    fn drop_leaves_nothing() {
+        let drop = redaction(RedactionPolicy::Drop, "");
+        for kind in [Kind::Email, Kind::Name, Kind::Token] {
+            assert_eq!(redact("ada@example.com", kind, &drop), "[redacted]");
+        }
+    }
+
+    #[test]
+    fn the_policy_is_read_from_lowercase_names() {
+        let policy: RedactionPolicy = serde_json::from_str(r#""mask""#).unwrap();
+        assert_eq!(policy, RedactionPolicy::Mask);
+        assert!(serde_json::from_str::<RedactionPolicy>(r#""Mask""#).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{RecursiveMode, Watcher};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use tracing_subscriber::EnvFilter;
+
+use crate::app::AppState;
+use crate::configuration::{get_configuration_from, RateLimitSettings, Settings};
+use crate::email_client::ValidEmail;
+use crate::telemetry;
+
+/// The part of `Settings` that can be changed without restarting the app.
+#[derive(Debug, Clone)]
+pub struct RuntimeSettings {
+    pub log_level: String,
+    pub rate_limits: RateLimitSettings,
+    pub email_sender: ValidEmail,
+    pub feature_flags: HashMap<String, bool>,
+}
+
+impl RuntimeSettings {
+    pub fn feature_enabled(&self, name: &str) -> bool {
+        self.feature_flags.get(name).copied().unwrap_or(false)
+    }
+}
+
+// Validates the reloadable settings, so a bad edit never reaches the running app.
+impl TryFrom<&Settings> for RuntimeSettings {
+    type Error = String;
+    fn try_from(settings: &Settings) -> Result<Self, Self::Error> {
+        let log_level = settings.application.log_level.clone();
+        EnvFilter::try_new(&log_level)
+            .map_err(|e| format!("Invalid log level `{}`: {}", log_level, e))?;
+
+        let email_sender = ValidEmail::new(&settings.email_client.sender)
+            .map_err(|e| format!("Invalid email sender: {}", e))?;
+
+        let rate_limits = settings.rate_limits.clone();
+        if rate_limits.window_seconds == 0 {
+            return Err("rate_limits.window_seconds must be greater than 0.".into());
+        }
+
+        Ok(Self {
+            log_level,
+            rate_limits,
+            email_sender,
+            feature_flags: settings.feature_flags.clone(),
+        })
+    }
+}
+
+/// Swap the runtime settings of a running app for the ones in `settings`.
+/// Nothing changes if they are invalid. Readers of `AppState::runtime`, the email
+/// client included, see either all of the old settings or all of the new ones.
+pub fn apply(state: &AppState, settings: &Settings) -> Result<(), String> {
+    let new = RuntimeSettings::try_from(settings)?;
+    let old = state.runtime.load();
+
+    // The log filter lives in the subscriber, not in `runtime`. It goes first, as
+    // the only step that can still fail.
+    if new.log_level != old.log_level {
+        telemetry::set_log_filter(&new.log_level)?;
+    }
+    state.runtime.store(Arc::new(new));
+    Ok(())
+}
+
+/// Reload the runtime settings from `conf_path` whenever a file in it changes
+/// or the process receives SIGHUP.
+pub fn spawn_watcher(state: Arc<AppState>, conf_path: PathBuf) -> Result<(), String> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let file_tx = tx.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if matches!(event, Ok(ref e) if e.kind.is_modify() || e.kind.is_create()) {
+            let _ = file_tx.send("file change");
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&conf_path, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+
+    let mut hangup = signal(SignalKind::hangup()).map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            let _ = tx.send("SIGHUP");
+        }
+    });
+
+    tokio::spawn(async move {
+        // Keep the watcher alive for as long as this task runs.
+        let _watcher = watcher;
+        while let Some(trigger) = rx.recv().await {
+            // Editors write a file in several steps, wait for them to settle.
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            while rx.try_recv().is_ok() {}
+
+            tracing::info!("Reloading configuration after {}.", trigger);
+            let result = get_configuration_from(&conf_path)
+                .map_err(|e| e.to_string())
+                .and_then(|settings| apply(&state, &settings));
+            match result {
+                Ok(()) => tracing::info!("Configuration reloaded."),
+                Err(e) => tracing::error!(
+                    "Rejected configuration reload, keeping the previous settings: {}",
+                    e
+                ),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::get_configuration;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn valid_settings_are_accepted() {
+        let settings = get_configuration().unwrap();
+        assert_ok!(RuntimeSettings::try_from(&settings));
+    }
+
+    #[test]
+    fn invalid_log_level_is_rejected() {
+        let mut settings = get_configuration().unwrap();
+        settings.application.log_level = "info,[".into();
+        assert_err!(RuntimeSettings::try_from(&settings));
+    }
+
+    #[test]
+    fn invalid_sender_is_rejected() {
+        let mut settings = get_configuration().unwrap();
+        settings.email_client.sender = "not-an-email".into();
+        assert_err!(RuntimeSettings::try_from(&settings));
+    }
+
+    #[test]
+    fn empty_rate_limit_window_is_rejected() {
+        let mut settings = get_configuration().unwrap();
+        settings.rate_limits.window_seconds = 0;
+        assert_err!(RuntimeSettings::try_from(&settings));
+    }
+
+    #[test]
+    fn unknown_feature_flags_are_disabled() {
+        let mut settings = get_configuration().unwrap();
+        settings.feature_flags.insert("enabled_flag".into(), true);
+        let runtime = RuntimeSettings::try_from(&settings).unwrap();
+        assert!(runtime.feature_enabled("enabled_flag"));
+        assert!(!runtime.feature_enabled("unknown_flag"));
+    }
+}
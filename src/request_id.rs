@@ -0,0 +1,50 @@
+//! Every request gets an id: the `X-Request-Id` it came with (e.g. from a load
+//! balancer) or a new UUID. It is a field of the request span, so all logs of the
+//! request carry it, handlers can read it with `Extension<RequestId>`, and it is
+//! sent back in the response, errors included.
+use axum::http::{HeaderName, Request};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::MakeSpan;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::telemetry;
+
+pub use tower_http::request_id::RequestId;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Sets the id of requests without one. It must wrap the other layers.
+pub fn set_request_id_layer() -> SetRequestIdLayer<MakeRequestUuid> {
+    SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid)
+}
+
+/// Copies the id of the request to the response.
+pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
+    PropagateRequestIdLayer::new(REQUEST_ID_HEADER)
+}
+
+/// The span of a request, for `TraceLayer`: the fields of `DefaultMakeSpan` and the id.
+/// Only the path of the URI is recorded, query strings carry tokens and searched emails.
+/// A request with a `traceparent` header continues the trace of the caller.
+#[derive(Debug, Clone, Default)]
+pub struct MakeRequestSpan;
+
+impl<B> MakeSpan<B> for MakeRequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .unwrap_or_default();
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method(),
+            path = %request.uri().path(),
+            version = ?request.version(),
+            request_id = %request_id,
+        );
+        span.set_parent(telemetry::extract_context(request.headers()));
+        span
+    }
+}
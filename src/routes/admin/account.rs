@@ -0,0 +1,173 @@
+use axum::extract::{Json, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum_macros::debug_handler;
+use secrecy::{ExposeSecret, Secret};
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::{
+    email_change, session, set_password, validate_credentials, AuthError, AuthenticatedUser,
+    Credentials,
+};
+use crate::email_client::ValidEmail;
+use crate::models;
+use crate::routes::admin::AdminError;
+
+/// Change the password of the logged in admin. Other sessions of the admin are ended.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Changing password",
+    skip(state, headers, user, payload),
+    fields(user_id = %user.user_id)
+)]
+pub async fn change_password(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    user: AuthenticatedUser,
+    context: AuditContext,
+    Json(payload): Json<models::ChangePasswordRequest>,
+) -> Result<StatusCode, AdminError> {
+    verify_current_password(&state, &user, payload.get_current_password()).await?;
+    if payload.get_new_password().expose_secret() == payload.get_current_password().expose_secret()
+    {
+        return Err(AdminError::Invalid(
+            "The new password must be different from the current one.".into(),
+        ));
+    }
+
+    let mut transaction = state.pg_pool.begin().await?;
+    set_password(
+        user.user_id,
+        payload.get_new_password().clone(),
+        &mut transaction,
+    )
+    .await?;
+    let jar = state.sessions.jar(&headers);
+    let ended = session::end_other_sessions(&mut transaction, &jar, user.user_id).await?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&user),
+        AuditEvent::new("user.change_password").target("user", user.user_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    tracing::info!("Password changed, ended {} other sessions.", ended);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Change the address password reset links are sent to. The new address is stored
+/// once the link emailed to it is followed, see `confirm_email`.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Changing email",
+    skip(state, user, payload),
+    fields(user_id = %user.user_id)
+)]
+pub async fn set_email(
+    State(state): State<Arc<app::AppState>>,
+    user: AuthenticatedUser,
+    context: AuditContext,
+    Json(payload): Json<models::ChangeEmailRequest>,
+) -> Result<StatusCode, AdminError> {
+    verify_current_password(&state, &user, payload.get_current_password()).await?;
+    let taken = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE email = $1 AND user_id <> $2) AS "taken!""#,
+        payload.get_email(),
+        user.user_id,
+    )
+    .fetch_one(&state.pg_pool)
+    .await?;
+    if taken {
+        return Err(AdminError::Conflict(
+            "Another admin uses this email.".into(),
+        ));
+    }
+    let recipient = ValidEmail::new(payload.get_email()).map_err(AdminError::Invalid)?;
+
+    let token =
+        email_change::create_email_change_token(&state.pg_pool, user.user_id, payload.get_email())
+            .await?;
+    let link = format!(
+        "{}/email/confirm?token={}",
+        state.base_url.0,
+        token.expose_secret()
+    );
+    state
+        .email_client
+        .send_email(
+            &recipient,
+            "Confirm your new email address",
+            &format!(
+                "Click <a href=\"{}\">here</a> to use this address for your admin account.<br />\
+                The link works once, within an hour. If you did not ask for it, ignore this email.",
+                link
+            ),
+            &format!(
+                "Visit {} to use this address for your admin account.\n\
+                The link works once, within an hour. If you did not ask for it, ignore this email.",
+                link
+            ),
+        )
+        .await
+        .map_err(|e| {
+            AdminError::Unexpected(format!("Failed to send the confirmation email: {}", e))
+        })?;
+    audit::record(
+        &state.pg_pool,
+        &context,
+        &Actor::from_user(&user),
+        AuditEvent::new("user.email_change_requested").target("user", user.user_id),
+    )
+    .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Store the new address of an email change with the token of its link. An unknown,
+/// used or expired token is `InvalidCredentials`.
+pub async fn confirm_email(
+    state: &app::AppState,
+    context: &AuditContext,
+    token: &str,
+) -> Result<(), AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let (user_id, username) = email_change::confirm_email_change(&mut transaction, token)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => {
+                AdminError::Conflict("Another admin uses this email.".into())
+            }
+            _ => e.into(),
+        })?
+        .ok_or(AuthError::InvalidCredentials)?;
+    audit::record(
+        &mut *transaction,
+        context,
+        &Actor::user(user_id, &username),
+        AuditEvent::new("user.set_email").target("user", user_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    tracing::info!(%user_id, "Email changed.");
+    Ok(())
+}
+
+async fn verify_current_password(
+    state: &app::AppState,
+    user: &AuthenticatedUser,
+    password: &Secret<String>,
+) -> Result<(), AdminError> {
+    let credentials = Credentials {
+        username: user.username.clone(),
+        password: password.clone(),
+    };
+    match validate_credentials(credentials, &state.pg_pool).await {
+        Ok(_) => Ok(()),
+        Err(AuthError::InvalidCredentials) => {
+            tracing::warn!("Wrong current password.");
+            Err(AdminError::Invalid("The current password is wrong.".into()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
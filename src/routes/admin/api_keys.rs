@@ -0,0 +1,89 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use secrecy::ExposeSecret;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::api_key::{self, ApiKeyInfo};
+use crate::authentication::{permissions, Authorized};
+use crate::models;
+use crate::routes::admin::AdminError;
+
+/// Create an API key. The response is the only time the key itself is shown.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Creating API key", skip(state, auth, payload), fields(user_id = %auth.user.user_id))]
+pub async fn create_api_key(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::ManageApiKeys>,
+    context: AuditContext,
+    Json(payload): Json<models::ApiKeyRequest>,
+) -> Result<(StatusCode, Json<Value>), AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let (api_key_id, key) = api_key::create_api_key(
+        &mut transaction,
+        payload.get_name(),
+        payload.get_scopes(),
+        auth.user.user_id,
+    )
+    .await?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("api_key.create")
+            .target("api_key", api_key_id)
+            .details(json!({ "name": payload.get_name(), "scopes": payload.get_scopes() })),
+    )
+    .await?;
+    transaction.commit().await?;
+    tracing::info!(%api_key_id, "API key created.");
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "api_key_id": api_key_id,
+            "key": key.expose_secret(),
+            "scopes": payload.get_scopes(),
+        })),
+    ))
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Listing API keys", skip(state, _auth))]
+pub async fn list_api_keys(
+    State(state): State<Arc<app::AppState>>,
+    _auth: Authorized<permissions::ManageApiKeys>,
+) -> Result<Json<Vec<ApiKeyInfo>>, AdminError> {
+    Ok(Json(api_key::list_api_keys(&state.pg_pool).await?))
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Revoking API key", skip(state, auth), fields(user_id = %auth.user.user_id))]
+pub async fn revoke_api_key(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::ManageApiKeys>,
+    context: AuditContext,
+    Path(api_key_id): Path<Uuid>,
+) -> Result<StatusCode, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    if api_key::revoke_api_key(&mut transaction, api_key_id).await? {
+        audit::record(
+            &mut *transaction,
+            &context,
+            &Actor::from_user(&auth.user),
+            AuditEvent::new("api_key.revoke").target("api_key", api_key_id),
+        )
+        .await?;
+        transaction.commit().await?;
+        tracing::info!("API key revoked.");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AdminError::NotFound(format!(
+            "There is no active API key with id {}.",
+            api_key_id
+        )))
+    }
+}
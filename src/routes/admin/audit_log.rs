@@ -0,0 +1,35 @@
+use axum::extract::{Json, Query, State};
+use axum_macros::debug_handler;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit;
+use crate::authentication::{permissions, Authorized};
+use crate::models;
+use crate::routes::admin::AdminError;
+
+/// Audit log entries, newest first. Pass `next_before` as `before` for the next page.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Listing audit log", skip(state, _auth))]
+pub async fn list_audit_log(
+    State(state): State<Arc<app::AppState>>,
+    _auth: Authorized<permissions::ViewAuditLog>,
+    Query(query): Query<models::AuditLogQuery>,
+) -> Result<Json<Value>, AdminError> {
+    let entries = audit::list_entries(
+        &state.pg_pool,
+        &query.get_filter(),
+        query.get_before(),
+        query.get_limit(),
+    )
+    .await?;
+    let next_before = if entries.len() as i64 == query.get_limit() {
+        entries.last().map(|entry| entry.audit_id)
+    } else {
+        None
+    };
+    Ok(Json(
+        json!({ "entries": entries, "next_before": next_before }),
+    ))
+}
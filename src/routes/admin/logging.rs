@@ -0,0 +1,56 @@
+use axum::extract::{Json, State};
+use axum_macros::debug_handler;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::{permissions, Authorized};
+use crate::models::LogLevelRequest;
+use crate::routes::admin::AdminError;
+use crate::telemetry;
+
+/// The log filter in effect.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Getting log level", skip(_auth))]
+pub async fn get_log_level(
+    _auth: Authorized<permissions::ManageLogging>,
+) -> Result<Json<Value>, AdminError> {
+    let directive = telemetry::log_filter()
+        .ok_or_else(|| AdminError::Unexpected("The log filter is not reloadable.".into()))?;
+    Ok(Json(json!({ "directive": directive })))
+}
+
+/// Replace the log filter until the next restart, or until `application.log_level`
+/// changes in the configuration.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Setting log level",
+    skip(state, auth),
+    fields(user_id = %auth.user.user_id)
+)]
+pub async fn set_log_level(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::ManageLogging>,
+    context: AuditContext,
+    Json(payload): Json<LogLevelRequest>,
+) -> Result<Json<Value>, AdminError> {
+    let previous = telemetry::log_filter();
+    telemetry::set_log_filter(payload.get_directive()).map_err(|e| {
+        AdminError::Invalid(format!(
+            "`{}` is not a valid log filter: {}",
+            payload.get_directive(),
+            e
+        ))
+    })?;
+    tracing::info!(directive = payload.get_directive(), "Log level changed.");
+    audit::record(
+        &state.pg_pool,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("log_level.set")
+            .details(json!({ "directive": payload.get_directive(), "previous": previous })),
+    )
+    .await?;
+    Ok(Json(json!({ "directive": telemetry::log_filter() })))
+}
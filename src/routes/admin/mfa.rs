@@ -0,0 +1,74 @@
+use axum::extract::{Json, State};
+use axum_macros::debug_handler;
+use secrecy::ExposeSecret;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::mfa::{self, TotpEnrolment};
+use crate::authentication::AuthenticatedUser;
+use crate::models;
+use crate::routes::admin::AdminError;
+
+/// Start TOTP enrolment. The secret is only required at login once it is confirmed.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Beginning TOTP enrolment",
+    skip(state, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn begin_totp_enrolment(
+    State(state): State<Arc<app::AppState>>,
+    user: AuthenticatedUser,
+    context: AuditContext,
+) -> Result<Json<TotpEnrolment>, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let enrolment =
+        mfa::begin_totp_enrolment(&mut transaction, &state.mfa, user.user_id, &user.username)
+            .await?
+            .ok_or_else(|| AdminError::Conflict("TOTP is already enabled.".into()))?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&user),
+        AuditEvent::new("mfa.totp_enrolment_started").target("user", user.user_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    Ok(Json(enrolment))
+}
+
+/// Enable TOTP with a first code from the authenticator app. Returns the recovery codes,
+/// which are not shown again.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Confirming TOTP enrolment",
+    skip(state, user, payload),
+    fields(user_id = %user.user_id)
+)]
+pub async fn confirm_totp_enrolment(
+    State(state): State<Arc<app::AppState>>,
+    user: AuthenticatedUser,
+    context: AuditContext,
+    Json(payload): Json<models::MfaCodeRequest>,
+) -> Result<Json<Value>, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let codes =
+        mfa::confirm_totp_enrolment(&mut transaction, &state.mfa, user.user_id, payload.get_code())
+            .await?
+            .ok_or_else(|| {
+                AdminError::Invalid("The code is wrong or there is no enrolment to confirm.".into())
+            })?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&user),
+        AuditEvent::new("mfa.totp_enabled").target("user", user.user_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    tracing::info!("TOTP enabled.");
+    let codes: Vec<&str> = codes.iter().map(|c| c.expose_secret().as_str()).collect();
+    Ok(Json(json!({ "recovery_codes": codes })))
+}
@@ -0,0 +1,69 @@
+pub mod account;
+pub mod api_keys;
+pub mod audit_log;
+pub mod logging;
+pub mod mfa;
+pub mod subscribers;
+pub mod users;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_macros::debug_handler;
+use serde_json::{json, Value};
+
+use crate::authentication::{AuthError, AuthenticatedUser};
+use crate::error::Problem;
+use crate::redact;
+
+/// Errors of the admin routes. Client errors become problem responses.
+#[derive(thiserror::Error, Debug)]
+pub enum AdminError {
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("sqlx: {0}")]
+    PostgreSQL(#[from] sqlx::Error),
+    #[error("unexpected: {0}")]
+    Unexpected(String),
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        match self {
+            AdminError::Auth(e) => e.into_response(),
+            AdminError::NotFound(detail) => {
+                Problem::new(StatusCode::NOT_FOUND, detail).into_response()
+            }
+            AdminError::Conflict(detail) => {
+                Problem::new(StatusCode::CONFLICT, detail).into_response()
+            }
+            AdminError::Invalid(detail) => {
+                Problem::new(StatusCode::UNPROCESSABLE_ENTITY, detail).into_response()
+            }
+            AdminError::PostgreSQL(e) => {
+                tracing::error!("Failed to execute query: {}", redact::DbError(&e));
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+            AdminError::Unexpected(e) => {
+                tracing::error!("Unexpected error: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// The admin that is making the request.
+#[debug_handler(state = std::sync::Arc<crate::app::AppState>)]
+pub async fn me(user: AuthenticatedUser) -> Json<Value> {
+    Json(json!({
+        "user_id": user.user_id,
+        "username": user.username,
+        "role": user.role,
+    }))
+}
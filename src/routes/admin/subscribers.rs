@@ -0,0 +1,485 @@
+use axum::extract::{Json, Path, Query, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent, AuditFilter};
+use crate::authentication::{permissions, Authorized};
+use crate::models::validation::SUBSCRIPTION_STATUSES;
+use crate::models::{SortOrder, SubscriberListQuery, SubscriberSort, SubscriberUpdateRequest};
+use crate::routes::admin::AdminError;
+use crate::routes::subscribe::{send_confirmation_email, store_token};
+use crate::token::generate_token;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Subscriber {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub subscribed_at: DateTime<Utc>,
+    pub status: String,
+}
+
+/// Subscribers matching the filters, one page at a time. The first page also has how
+/// many match per status.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Listing subscribers", skip(state, _auth))]
+pub async fn list_subscribers(
+    State(state): State<Arc<app::AppState>>,
+    _auth: Authorized<permissions::ListSubscribers>,
+    Query(query): Query<SubscriberListQuery>,
+) -> Result<Json<Value>, AdminError> {
+    Ok(Json(subscribers_page(&state.pg_pool, &query).await?))
+}
+
+/// The body of the subscriber listings, for the admin and the API routes.
+pub async fn subscribers_page(
+    pool: &PgPool,
+    query: &SubscriberListQuery,
+) -> Result<Value, AdminError> {
+    let (subscribers, next_cursor) = list_page(pool, query).await?;
+    let mut page = json!({
+        "subscribers": subscribers,
+        "next_cursor": next_cursor,
+    });
+    // The counts scan every match, so they are not repeated for the following pages.
+    if query.get_cursor().is_none() {
+        page["counts"] = Value::Object(count_per_status(pool, query).await?);
+    }
+    Ok(page)
+}
+
+/// A subscriber with the number of its pending confirmation tokens and the admin
+/// actions taken on it.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Getting subscriber", skip(state, _auth))]
+pub async fn get_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    _auth: Authorized<permissions::ListSubscribers>,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<Value>, AdminError> {
+    let subscriber = fetch_subscriber(&state.pg_pool, subscriber_id).await?;
+    // The tokens themselves would let anyone with read access confirm the subscriber.
+    let tokens = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!", max(created_at) AS last_created_at
+        FROM subscription_tokens WHERE subscriber_id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_one(&state.pg_pool)
+    .await?;
+    let target_id = subscriber_id.to_string();
+    let filter = AuditFilter {
+        target_type: Some("subscriber"),
+        target_id: Some(&target_id),
+        ..Default::default()
+    };
+    let history = audit::list_entries(&state.pg_pool, &filter, None, 200).await?;
+    Ok(Json(json!({
+        "subscriber": subscriber,
+        "tokens": {
+            "count": tokens.count,
+            "last_created_at": tokens.last_created_at,
+        },
+        "history": history,
+    })))
+}
+
+/// Confirm a subscriber without the link from the email.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Confirming subscriber manually", skip(state, auth), fields(user_id = %auth.user.user_id))]
+pub async fn confirm_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::EditSubscribers>,
+    context: AuditContext,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<Subscriber>, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let subscriber = set_status(&mut transaction, subscriber_id, "confirmed").await?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("subscriber.confirm").target("subscriber", subscriber_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    metrics::increment_counter!("subscriptions_confirmed_total", "source" => "admin");
+    Ok(Json(subscriber))
+}
+
+/// Stop sending issues to a subscriber. The row is kept, unlike with a delete.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Unsubscribing subscriber", skip(state, auth), fields(user_id = %auth.user.user_id))]
+pub async fn unsubscribe_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::EditSubscribers>,
+    context: AuditContext,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<Subscriber>, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let subscriber = set_status(&mut transaction, subscriber_id, "unsubscribed").await?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("subscriber.unsubscribe").target("subscriber", subscriber_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    Ok(Json(subscriber))
+}
+
+/// Send a new confirmation link to a subscriber who has not confirmed yet.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Resending confirmation email", skip(state, auth), fields(user_id = %auth.user.user_id))]
+pub async fn resend_confirmation(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::EditSubscribers>,
+    context: AuditContext,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<StatusCode, AdminError> {
+    let subscriber = fetch_subscriber(&state.pg_pool, subscriber_id).await?;
+    if subscriber.status != "pending_confirmation" {
+        return Err(AdminError::Conflict(format!(
+            "The subscriber is {}, not pending confirmation.",
+            subscriber.status
+        )));
+    }
+
+    let token = generate_token();
+    let mut transaction = state.pg_pool.begin().await?;
+    store_token(&mut transaction, subscriber_id, &token).await?;
+    transaction.commit().await?;
+    let status = send_confirmation_email(
+        &state.base_url,
+        &state.email_client,
+        &subscriber.email,
+        &token,
+    )
+    .await;
+    if status != StatusCode::OK {
+        return Err(AdminError::Unexpected(
+            "Failed to send the confirmation email.".into(),
+        ));
+    }
+
+    audit::record(
+        &state.pg_pool,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("subscriber.resend_confirmation").target("subscriber", subscriber_id),
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Editing subscriber", skip(state, auth), fields(user_id = %auth.user.user_id))]
+pub async fn update_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::EditSubscribers>,
+    context: AuditContext,
+    Path(subscriber_id): Path<Uuid>,
+    Json(payload): Json<SubscriberUpdateRequest>,
+) -> Result<Json<Subscriber>, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let subscriber = set_name(&mut transaction, subscriber_id, payload.get_name()).await?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("subscriber.update").target("subscriber", subscriber_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    Ok(Json(subscriber))
+}
+
+/// Remove a subscriber and its tokens for good, e.g. for an erasure request.
+/// The audit entry only keeps the id, not the email or name.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Deleting subscriber", skip(state, auth), fields(user_id = %auth.user.user_id))]
+pub async fn delete_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::DeleteSubscribers>,
+    context: AuditContext,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<StatusCode, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    // The tokens reference the subscriber without ON DELETE CASCADE, so they go first.
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    let result = sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(subscriber_not_found(subscriber_id));
+    }
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("subscriber.delete").target("subscriber", subscriber_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    tracing::info!("Subscriber deleted.");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn fetch_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Subscriber, AdminError> {
+    sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, email, name, subscribed_at, status FROM subscriptions WHERE id = $1"#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| subscriber_not_found(subscriber_id))
+}
+
+pub async fn set_name(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    name: &str,
+) -> Result<Subscriber, AdminError> {
+    sqlx::query_as!(
+        Subscriber,
+        r#"
+        UPDATE subscriptions SET name = $2 WHERE id = $1
+        RETURNING id, email, name, subscribed_at, status
+        "#,
+        subscriber_id,
+        name,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| subscriber_not_found(subscriber_id))
+}
+
+// Outstanding confirmation links are removed, so an old link cannot undo the new status.
+pub async fn set_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    status: &str,
+) -> Result<Subscriber, AdminError> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        UPDATE subscriptions SET status = $2 WHERE id = $1
+        RETURNING id, email, name, subscribed_at, status
+        "#,
+        subscriber_id,
+        status,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .ok_or_else(|| subscriber_not_found(subscriber_id))?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    tracing::info!(status, "Subscriber status changed.");
+    Ok(subscriber)
+}
+
+fn subscriber_not_found(subscriber_id: Uuid) -> AdminError {
+    AdminError::NotFound(format!("There is no subscriber with id {}.", subscriber_id))
+}
+
+/// One page of subscribers, and the cursor of the next page if there is one.
+pub async fn list_page(
+    pool: &PgPool,
+    query: &SubscriberListQuery,
+) -> Result<(Vec<Subscriber>, Option<String>), AdminError> {
+    let after = query.get_cursor().map(Cursor::decode).transpose()?;
+    let mut subscribers = fetch_page(pool, query, after.as_ref()).await?;
+
+    // One extra row was fetched to know if there is a next page.
+    let next_cursor = if subscribers.len() as i64 > query.get_limit() {
+        subscribers.truncate(query.get_limit() as usize);
+        subscribers
+            .last()
+            .map(|last| Cursor::after(last, query.get_sort()).encode())
+    } else {
+        None
+    };
+    Ok((subscribers, next_cursor))
+}
+
+/// Where the previous page ended: its last row's sort key and id.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Cursor {
+    key: String,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn after(subscriber: &Subscriber, sort: SubscriberSort) -> Self {
+        let key = match sort {
+            SubscriberSort::SubscribedAt => subscriber.subscribed_at.to_rfc3339(),
+            SubscriberSort::Email => subscriber.email.clone(),
+            SubscriberSort::Name => subscriber.name.clone(),
+        };
+        Self {
+            key,
+            id: subscriber.id,
+        }
+    }
+
+    fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("A cursor is always serializable.");
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    fn decode(cursor: &str) -> Result<Self, AdminError> {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| AdminError::Invalid("The cursor is not valid.".into()))
+    }
+}
+
+#[tracing::instrument(name = "Fetch subscriber page", skip(pool))]
+async fn fetch_page(
+    pool: &PgPool,
+    query: &SubscriberListQuery,
+    after: Option<&Cursor>,
+) -> Result<Vec<Subscriber>, AdminError> {
+    let column = query.get_sort().column();
+    let (comparison, direction) = match query.get_order() {
+        SortOrder::Asc => (">", "ASC"),
+        SortOrder::Desc => ("<", "DESC"),
+    };
+
+    let mut builder =
+        QueryBuilder::new("SELECT id, email, name, subscribed_at, status FROM subscriptions");
+    push_filters(&mut builder, query, true);
+    if let Some(cursor) = after {
+        // A row comparison, so the (column, id) index can be used to seek to the page.
+        builder.push(format!(" AND ({}, id) {} (", column, comparison));
+        match query.get_sort() {
+            SubscriberSort::SubscribedAt => {
+                let key = DateTime::parse_from_rfc3339(&cursor.key)
+                    .map_err(|_| AdminError::Invalid("The cursor is not valid.".into()))?;
+                builder.push_bind(key.with_timezone(&Utc));
+            }
+            SubscriberSort::Email | SubscriberSort::Name => {
+                builder.push_bind(cursor.key.clone());
+            }
+        }
+        builder.push(", ").push_bind(cursor.id).push(")");
+    }
+    builder.push(format!(
+        " ORDER BY {} {}, id {} LIMIT ",
+        column, direction, direction
+    ));
+    builder.push_bind(query.get_limit() + 1);
+
+    Ok(builder
+        .build_query_as::<Subscriber>()
+        .fetch_all(pool)
+        .await?)
+}
+
+// The counts ignore the status filter, so they show what each status would return.
+// Without other filters they come from the (status, subscribed_at, id) index alone.
+#[tracing::instrument(name = "Count subscribers per status", skip(pool))]
+pub async fn count_per_status(
+    pool: &PgPool,
+    query: &SubscriberListQuery,
+) -> Result<Map<String, Value>, AdminError> {
+    let mut builder = QueryBuilder::new("SELECT status, count(*) FROM subscriptions");
+    push_filters(&mut builder, query, false);
+    builder.push(" GROUP BY status");
+    let rows: Vec<(String, i64)> = builder.build_query_as().fetch_all(pool).await?;
+
+    let mut counts: Map<String, Value> = SUBSCRIPTION_STATUSES
+        .iter()
+        .map(|status| (status.to_string(), json!(0)))
+        .collect();
+    for (status, count) in rows {
+        counts.insert(status, json!(count));
+    }
+    Ok(counts)
+}
+
+fn push_filters(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    query: &SubscriberListQuery,
+    with_status: bool,
+) {
+    builder.push(" WHERE TRUE");
+    if let Some(status) = query.get_status().filter(|_| with_status) {
+        builder.push(" AND status = ").push_bind(status.to_string());
+    }
+    if let Some(from) = query.get_subscribed_from() {
+        builder.push(" AND subscribed_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.get_subscribed_to() {
+        builder.push(" AND subscribed_at < ").push_bind(to);
+    }
+    if let Some(search) = query.get_search() {
+        let pattern = format!("%{}%", escape_like(search));
+        builder
+            .push(" AND (email ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR name ILIKE ")
+            .push_bind(pattern)
+            .push(")");
+    }
+}
+
+// Search terms are matched literally, `%` and `_` are not wildcards.
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claim::assert_err;
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = Cursor {
+            key: "ursula@example.com".into(),
+            id: Uuid::new_v4(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    }
+
+    #[test]
+    fn garbage_cursors_are_rejected() {
+        assert_err!(Cursor::decode("not a cursor"));
+        assert_err!(Cursor::decode(
+            &base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("{}")
+        ));
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("100%_off\\"), "100\\%\\_off\\\\");
+    }
+}
@@ -0,0 +1,64 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::{permissions, Authorized, Role};
+use crate::models;
+use crate::routes::admin::AdminError;
+
+/// Change the role of an admin user.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Changing user role",
+    skip(state, auth, payload),
+    fields(actor_id = %auth.user.user_id, role = payload.get_role().as_str())
+)]
+pub async fn set_role(
+    State(state): State<Arc<app::AppState>>,
+    auth: Authorized<permissions::ManageUsers>,
+    context: AuditContext,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<models::RoleRequest>,
+) -> Result<StatusCode, AdminError> {
+    let mut transaction = state.pg_pool.begin().await?;
+    // Lock the owners, so two demotions at the same time cannot remove the last one.
+    let owners = sqlx::query!(r#"SELECT user_id FROM users WHERE role = 'owner' FOR UPDATE"#)
+        .fetch_all(&mut *transaction)
+        .await?;
+    if payload.get_role() != Role::Owner && owners.len() == 1 && owners[0].user_id == user_id {
+        return Err(AdminError::Conflict(
+            "The last owner cannot be demoted.".into(),
+        ));
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE users SET role = $2 WHERE user_id = $1"#,
+        user_id,
+        payload.get_role().as_str(),
+    )
+    .execute(&mut *transaction)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AdminError::NotFound(format!(
+            "There is no user with id {}.",
+            user_id
+        )));
+    }
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::from_user(&auth.user),
+        AuditEvent::new("user.set_role")
+            .target("user", user_id)
+            .details(json!({ "role": payload.get_role() })),
+    )
+    .await?;
+    transaction.commit().await?;
+    tracing::info!("User role changed.");
+    Ok(StatusCode::NO_CONTENT)
+}
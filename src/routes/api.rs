@@ -0,0 +1,111 @@
+use axum::extract::{Json, Path, Query, State};
+use axum_macros::debug_handler;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::{ApiClient, Scope};
+use crate::models::{SubscriberListQuery, SubscriberUpdateRequest};
+use crate::routes::admin::subscribers::{
+    fetch_subscriber, set_name, set_status, subscribers_page, Subscriber,
+};
+use crate::routes::admin::AdminError;
+
+/// The API key that is making the request, so clients can check their setup.
+#[debug_handler(state = Arc<app::AppState>)]
+pub async fn me(client: ApiClient) -> Json<Value> {
+    Json(json!({
+        "api_key_id": client.api_key_id,
+        "scopes": client.scopes,
+    }))
+}
+
+/// Subscribers, filtered and paged like `GET /admin/subscribers`. Needs `subscribers:read`.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Listing subscribers with an API key",
+    skip(state, client),
+    fields(api_key_id = %client.api_key_id)
+)]
+pub async fn list_subscribers(
+    State(state): State<Arc<app::AppState>>,
+    client: ApiClient,
+    Query(query): Query<SubscriberListQuery>,
+) -> Result<Json<Value>, AdminError> {
+    client.require_scope(Scope::SubscribersRead)?;
+    Ok(Json(subscribers_page(&state.pg_pool, &query).await?))
+}
+
+/// Needs `subscribers:read`.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Getting subscriber with an API key",
+    skip(state, client),
+    fields(api_key_id = %client.api_key_id)
+)]
+pub async fn get_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    client: ApiClient,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<Subscriber>, AdminError> {
+    client.require_scope(Scope::SubscribersRead)?;
+    Ok(Json(fetch_subscriber(&state.pg_pool, subscriber_id).await?))
+}
+
+/// Change a subscriber's name, like `PATCH /admin/subscribers/:subscriber_id`.
+/// Needs `subscribers:write`.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Editing subscriber with an API key",
+    skip(state, client),
+    fields(api_key_id = %client.api_key_id)
+)]
+pub async fn update_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    client: ApiClient,
+    context: AuditContext,
+    Path(subscriber_id): Path<Uuid>,
+    Json(payload): Json<SubscriberUpdateRequest>,
+) -> Result<Json<Subscriber>, AdminError> {
+    client.require_scope(Scope::SubscribersWrite)?;
+    let mut transaction = state.pg_pool.begin().await?;
+    let subscriber = set_name(&mut transaction, subscriber_id, payload.get_name()).await?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::api_key(client.api_key_id),
+        AuditEvent::new("subscriber.update").target("subscriber", subscriber_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    Ok(Json(subscriber))
+}
+
+/// Needs `subscribers:write`, e.g. for a CMS that handles unsubscribe requests itself.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Unsubscribing subscriber with an API key",
+    skip(state, client),
+    fields(api_key_id = %client.api_key_id)
+)]
+pub async fn unsubscribe_subscriber(
+    State(state): State<Arc<app::AppState>>,
+    client: ApiClient,
+    context: AuditContext,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<Subscriber>, AdminError> {
+    client.require_scope(Scope::SubscribersWrite)?;
+    let mut transaction = state.pg_pool.begin().await?;
+    let subscriber = set_status(&mut transaction, subscriber_id, "unsubscribed").await?;
+    audit::record(
+        &mut *transaction,
+        &context,
+        &Actor::api_key(client.api_key_id),
+        AuditEvent::new("subscriber.unsubscribe").target("subscriber", subscriber_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    Ok(Json(subscriber))
+}
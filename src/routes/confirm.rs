@@ -9,6 +9,7 @@ use std::sync::Arc;
 
 use crate::app;
 use crate::models;
+use crate::redact;
 
 
 
@@ -19,9 +20,7 @@ use crate::models;
     name = "Confirming Subscription",
     skip(state, query),
     fields(
-        // could not find a way to get this automatically with tower_http like in the book.
-        request_id = %Uuid::new_v4(), 
-        token = %query.get_token(),
+        token = %redact::Token(query.get_token()),
         // subscriber_name= %payload.get_name()
     )
 )]
@@ -42,6 +41,7 @@ pub async fn confirm_subscription(
             if confirm_subscriber(&state.pg_pool, subscriber_id).await.is_err() {
                 return StatusCode::INTERNAL_SERVER_ERROR;
             }
+            metrics::increment_counter!("subscriptions_confirmed_total", "source" => "link");
             StatusCode::OK
         }
     }
@@ -64,9 +64,8 @@ pub async fn confirm_subscriber(
     )
     .execute(pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
+    .inspect_err(|e| {
+        tracing::error!("Failed to execute query: {}", redact::DbError(e));
     })?;
     Ok(())
 }
@@ -85,9 +84,8 @@ pub async fn get_subscriber_id_from_token(
         )
         .fetch_optional(pool)
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to execute query: {:?}", e);
-            e
+        .inspect_err(|e| {
+            tracing::error!("Failed to execute query: {}", redact::DbError(e));
         })?;
     Ok(result.map(|r| r.subscriber_id))
 }
\ No newline at end of file
@@ -0,0 +1,167 @@
+//! Probes for the orchestrator. `/health/live` only says that the process answers,
+//! `/health/ready` checks what the app needs to do its work.
+//!
+//! The database and its migrations are critical: without them no request can be
+//! served, so the instance answers 503 and is taken out of rotation. The email
+//! provider and the worker are shared by all instances, so restarting or draining
+//! one would not help; their failures make the status `degraded` but keep a 200.
+//!
+//! The probe is public, so its body only says which component is up and how fast it
+//! answered. Why a component is down is logged instead.
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum_macros::debug_handler;
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::AppState;
+use crate::{migrations, worker};
+
+/// Longer checks count as failed, so a hanging dependency cannot hang the probe.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// The email provider is asked at most this often, however often the probe runs.
+pub const EMAIL_PROVIDER_CHECK_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ComponentStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Check {
+    status: ComponentStatus,
+    #[serde(skip)]
+    critical: bool,
+    latency_ms: f64,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+// Run `check` with the timeout and measure it. It fails with the reason the
+// component is down.
+async fn run_check<F>(critical: bool, check: F) -> Check
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("No answer within {:?}.", CHECK_TIMEOUT)));
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let (status, error) = match result {
+        Ok(()) => (ComponentStatus::Up, None),
+        Err(e) => (ComponentStatus::Down, Some(e)),
+    };
+    Check {
+        status,
+        critical,
+        latency_ms,
+        error,
+    }
+}
+
+/// The last check of the email provider, reused for `EMAIL_PROVIDER_CHECK_TTL`.
+/// Clones share it.
+#[derive(Debug, Clone, Default)]
+pub struct EmailProviderCheck(Arc<Mutex<Option<(Instant, Check)>>>);
+
+impl EmailProviderCheck {
+    async fn run(&self, state: &AppState) -> Check {
+        if let Some((checked_at, check)) = &*self.0.lock().unwrap() {
+            if checked_at.elapsed() < EMAIL_PROVIDER_CHECK_TTL {
+                return check.clone();
+            }
+        }
+        let check = run_check(false, async {
+            state
+                .email_client
+                .check_reachable()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await;
+        *self.0.lock().unwrap() = Some((Instant::now(), check.clone()));
+        check
+    }
+}
+
+/// The process is up and serving requests.
+#[debug_handler]
+pub async fn live() -> Json<Value> {
+    Json(json!({ "status": "live" }))
+}
+
+/// Check every dependency, see the module documentation for what fails the probe.
+#[debug_handler(state = Arc<AppState>)]
+#[tracing::instrument(name = "Checking readiness", skip(state))]
+pub async fn ready(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let pool = &state.pg_pool;
+    let (database, migrations, email_provider, worker) = tokio::join!(
+        run_check(true, async {
+            sqlx::query("SELECT 1")
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        }),
+        run_check(true, async {
+            let pending = migrations::pending(pool).await.map_err(|e| e.to_string())?;
+            if pending.is_empty() {
+                Ok(())
+            } else {
+                Err(format!("Migrations {:?} are not applied.", pending))
+            }
+        }),
+        state.email_provider_check.run(&state),
+        run_check(false, async {
+            let last = worker::last_heartbeat(pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("No worker has recorded a heartbeat.")?;
+            let age = (Utc::now() - last).to_std().unwrap_or_default();
+            if age > worker::HEARTBEAT_TIMEOUT {
+                return Err(format!("The last heartbeat is from {}.", last.to_rfc3339()));
+            }
+            Ok(())
+        }),
+    );
+
+    let checks = [
+        ("database", database),
+        ("migrations", migrations),
+        ("email_provider", email_provider),
+        ("worker", worker),
+    ];
+    let critical_down = checks
+        .iter()
+        .any(|(_, check)| check.critical && check.status == ComponentStatus::Down);
+    let any_down = checks
+        .iter()
+        .any(|(_, check)| check.status == ComponentStatus::Down);
+    let (code, status) = if critical_down {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    } else if any_down {
+        (StatusCode::OK, "degraded")
+    } else {
+        (StatusCode::OK, "ready")
+    };
+    for (name, check) in checks
+        .iter()
+        .filter(|(_, c)| c.status == ComponentStatus::Down)
+    {
+        tracing::warn!(component = name, error = ?check.error, "Readiness check failed.");
+    }
+
+    let checks: Map<String, Value> = checks
+        .into_iter()
+        .map(|(name, check)| (name.to_string(), json!(check)))
+        .collect();
+    (code, Json(json!({ "status": status, "checks": checks })))
+}
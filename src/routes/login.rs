@@ -0,0 +1,143 @@
+use axum::extract::{Json, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum_extra::extract::cookie::PrivateCookieJar;
+use axum_macros::debug_handler;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::{mfa, session, validate_credentials, AuthError, Credentials};
+use crate::models;
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Logging in",
+    skip(state, headers, payload),
+    fields(username = %payload.get_username(), user_id = tracing::field::Empty)
+)]
+pub async fn login(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    context: AuditContext,
+    Json(payload): Json<models::LoginRequest>,
+) -> Result<(PrivateCookieJar, Json<Value>), AuthError> {
+    let jar = state.sessions.jar(&headers);
+    let (jar, mfa_required) =
+        authenticate(&state, jar, &context, payload.into_credentials()).await?;
+    Ok((jar, Json(json!({ "mfa_required": mfa_required }))))
+}
+
+/// The second login step for users with TOTP: a code from their app or a recovery code.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Verifying second factor",
+    skip(state, headers, payload),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn login_mfa(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    context: AuditContext,
+    Json(payload): Json<models::MfaCodeRequest>,
+) -> Result<(PrivateCookieJar, StatusCode), AuthError> {
+    let jar = state.sessions.jar(&headers);
+    let jar = complete_second_factor(&state, jar, &context, payload.get_code()).await?;
+    Ok((jar, StatusCode::OK))
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Logging out", skip(state, headers))]
+pub async fn logout(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+) -> Result<(PrivateCookieJar, StatusCode), AuthError> {
+    let jar = state.sessions.jar(&headers);
+    let jar = session::end_session(&state.pg_pool, jar).await?;
+    Ok((jar, StatusCode::OK))
+}
+
+/// Check the password and start a session, which waits for the second factor if the
+/// user has TOTP. Returns the jar with the session cookie and whether it is waiting.
+/// Shared by the JSON and the HTML login.
+pub async fn authenticate(
+    state: &app::AppState,
+    jar: PrivateCookieJar,
+    context: &AuditContext,
+    credentials: Credentials,
+) -> Result<(PrivateCookieJar, bool), AuthError> {
+    let username = credentials.username.clone();
+    let user_id = match validate_credentials(credentials, &state.pg_pool).await {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            if let AuthError::InvalidCredentials = e {
+                audit::record(
+                    &state.pg_pool,
+                    context,
+                    &Actor::anonymous(&username),
+                    AuditEvent::new("login.failure"),
+                )
+                .await?;
+            }
+            return Err(e);
+        }
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    // Users with TOTP get a session that can only be used for the second factor.
+    let mfa_required = mfa::is_totp_enabled(&state.pg_pool, user_id).await?;
+    let jar =
+        session::start_session(&state.pg_pool, &state.sessions, jar, user_id, mfa_required).await?;
+    let action = if mfa_required {
+        tracing::info!("Password accepted, waiting for the second factor.");
+        "login.password_accepted"
+    } else {
+        tracing::info!("Login succeeded.");
+        "login.success"
+    };
+    audit::record(
+        &state.pg_pool,
+        context,
+        &Actor::user(user_id, &username),
+        AuditEvent::new(action),
+    )
+    .await?;
+    Ok((jar, mfa_required))
+}
+
+/// Replace the session waiting for the second factor with a full one if `code` is right.
+pub async fn complete_second_factor(
+    state: &app::AppState,
+    jar: PrivateCookieJar,
+    context: &AuditContext,
+    code: &str,
+) -> Result<PrivateCookieJar, AuthError> {
+    let (user_id, username) = session::get_mfa_pending_user(&state.pg_pool, &jar)
+        .await?
+        .ok_or(AuthError::MissingCredentials)?;
+    let actor = Actor::user(user_id, &username);
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    if !mfa::verify_second_factor(&state.pg_pool, &state.mfa, user_id, code).await? {
+        session::record_failed_mfa_attempt(&state.pg_pool, &jar).await?;
+        audit::record(
+            &state.pg_pool,
+            context,
+            &actor,
+            AuditEvent::new("login.mfa_failure"),
+        )
+        .await?;
+        return Err(AuthError::InvalidCredentials);
+    }
+    // A new session id, so the pending one cannot be used after this.
+    let jar = session::start_session(&state.pg_pool, &state.sessions, jar, user_id, false).await?;
+    tracing::info!("Login succeeded.");
+    audit::record(
+        &state.pg_pool,
+        context,
+        &actor,
+        AuditEvent::new("login.success").details(json!({ "second_factor": true })),
+    )
+    .await?;
+    Ok(jar)
+}
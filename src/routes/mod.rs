@@ -1,3 +1,9 @@
+pub mod admin;
+pub mod api;
 pub mod confirm;
+pub mod health;
+pub mod login;
+pub mod pages;
+pub mod password;
 pub mod subscribe;
 pub mod utils;
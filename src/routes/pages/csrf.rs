@@ -0,0 +1,81 @@
+//! CSRF protection for the forms of the pages, with the synchronizer token pattern.
+//! The token is kept in an encrypted cookie and repeated in a hidden form field; a
+//! form is only accepted if both match. The session cookie is `SameSite=Strict`
+//! already, this also covers the login form, which has no session yet.
+use axum_extra::extract::cookie::PrivateCookieJar;
+use base64::Engine;
+use rand::Rng;
+
+use crate::authentication::SessionConfig;
+use crate::routes::pages::PageError;
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+/// The name of the hidden form field.
+pub const CSRF_FIELD: &str = "csrf_token";
+
+/// The token of the jar, or a new one that is added to it.
+pub fn token(config: &SessionConfig, jar: PrivateCookieJar) -> (PrivateCookieJar, String) {
+    if let Some(cookie) = jar.get(CSRF_COOKIE) {
+        let token = cookie.value().to_string();
+        return (jar, token);
+    }
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    let token = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+    let jar = jar.add(config.cookie(CSRF_COOKIE, token.clone()));
+    (jar, token)
+}
+
+/// Check the token submitted with a form against the one of the jar.
+pub fn verify(jar: &PrivateCookieJar, submitted: &str) -> Result<(), PageError> {
+    match jar.get(CSRF_COOKIE) {
+        Some(cookie) if constant_time_eq(cookie.value(), submitted) => Ok(()),
+        _ => Err(PageError::Csrf),
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (x, y)| difference | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use axum_extra::extract::cookie::Key;
+
+    fn config() -> SessionConfig {
+        SessionConfig {
+            key: Key::generate(),
+            idle_timeout: chrono::Duration::minutes(30),
+            absolute_timeout: chrono::Duration::hours(12),
+            secure_cookie: true,
+        }
+    }
+
+    #[test]
+    fn the_token_is_kept_in_the_jar() {
+        let config = config();
+        let jar = config.jar(&HeaderMap::new());
+
+        let (jar, first) = token(&config, jar);
+        let (jar, second) = token(&config, jar);
+
+        assert_eq!(first, second);
+        assert!(verify(&jar, &first).is_ok());
+    }
+
+    #[test]
+    fn other_or_missing_tokens_are_rejected() {
+        let config = config();
+        let (jar, token) = token(&config, config.jar(&HeaderMap::new()));
+
+        assert!(verify(&jar, "").is_err());
+        assert!(verify(&jar, &token[1..]).is_err());
+        assert!(verify(&jar, &token.replace(&token[..1], "!")).is_err());
+        assert!(verify(&config.jar(&HeaderMap::new()), &token).is_err());
+    }
+}
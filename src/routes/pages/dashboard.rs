@@ -0,0 +1,42 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Html;
+use axum_extra::extract::cookie::PrivateCookieJar;
+use axum_macros::debug_handler;
+use std::sync::Arc;
+
+use crate::app;
+use crate::authentication::permissions;
+use crate::models::SubscriberListQuery;
+use crate::routes::admin::subscribers::count_per_status;
+use crate::routes::pages::html::escape;
+use crate::routes::pages::{PageAuth, PageContext, PageError};
+
+/// Subscriber counts by status.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Showing dashboard", skip(state, headers, auth), fields(user_id = %auth.user.user_id))]
+pub async fn dashboard(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    auth: PageAuth<permissions::ListSubscribers>,
+) -> Result<(PrivateCookieJar, Html<String>), PageError> {
+    let page = PageContext::new(&state, &headers);
+    let counts = count_per_status(&state.pg_pool, &SubscriberListQuery::default()).await?;
+
+    let rows: String = counts
+        .iter()
+        .map(|(status, count)| {
+            format!(
+                r#"<tr><td><a href="/dashboard/subscribers?status={status}">{status}</a></td><td>{count}</td></tr>"#,
+                status = escape(status),
+                count = count,
+            )
+        })
+        .collect();
+    let body = format!(
+        r#"<h2>Subscribers</h2>
+<table><thead><tr><th>Status</th><th>Subscribers</th></tr></thead><tbody>{}</tbody></table>"#,
+        rows
+    );
+    Ok(page.render("Dashboard", Some(&auth.user), &body))
+}
@@ -0,0 +1,62 @@
+use axum::extract::{Form, Query, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum_macros::debug_handler;
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit::AuditContext;
+use crate::authentication::AuthError;
+use crate::models::{CsrfForm, TokenQuery};
+use crate::routes::admin::account::confirm_email;
+use crate::routes::admin::AdminError;
+use crate::routes::pages::flash::Flash;
+use crate::routes::pages::html::{csrf_field, escape};
+use crate::routes::pages::{csrf, redirect_with, PageContext, PageError, LOGIN_PAGE};
+
+const CONFIRM_FORM: &str = "/dashboard/email/confirm";
+
+/// The page the email change confirmation links to. Opening it changes nothing, so
+/// link previews of mail clients do not confirm by accident.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Showing email confirmation page", skip_all)]
+pub async fn confirm_email_page(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<TokenQuery>,
+) -> Response {
+    let page = PageContext::new(&state, &headers);
+    let body = format!(
+        r#"<form method="post" action="{}">
+{}
+<input type="hidden" name="token" value="{}">
+<p><button type="submit">Use this address</button></p>
+</form>"#,
+        CONFIRM_FORM,
+        csrf_field(page.csrf_token()),
+        escape(query.get_token()),
+    );
+    page.render("Confirm your new email address", None, &body)
+        .into_response()
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Confirming an email change with the page", skip_all)]
+pub async fn confirm_email_change(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    context: AuditContext,
+    Form(form): Form<CsrfForm<TokenQuery>>,
+) -> Result<Response, PageError> {
+    let jar = state.sessions.jar(&headers);
+    csrf::verify(&jar, form.get_csrf_token())?;
+    let flash = match confirm_email(&state, &context, form.get_payload().get_token()).await {
+        Ok(()) => Flash::success("Your email address has been changed."),
+        Err(AdminError::Auth(AuthError::InvalidCredentials)) => Flash::error(
+            "The confirmation link is invalid, used or expired. Change the address again.",
+        ),
+        Err(AdminError::Conflict(detail)) => Flash::error(detail),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(redirect_with(&state.sessions, jar, flash, LOGIN_PAGE).into_response())
+}
@@ -0,0 +1,107 @@
+//! One-time messages shown on the page after a form, e.g. "Subscriber updated.". They are
+//! kept in an encrypted cookie between the redirect and the next page.
+use axum_extra::extract::cookie::PrivateCookieJar;
+
+use crate::authentication::SessionConfig;
+
+pub const FLASH_COOKIE: &str = "flash";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Success,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Success => "success",
+            Level::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flash {
+    pub level: Level,
+    pub message: String,
+}
+
+impl Flash {
+    pub fn success(message: impl Into<String>) -> Self {
+        Self {
+            level: Level::Success,
+            message: message.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            level: Level::Error,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn set(config: &SessionConfig, jar: PrivateCookieJar, flash: Flash) -> PrivateCookieJar {
+    let value = format!("{}:{}", flash.level.as_str(), flash.message);
+    jar.add(config.cookie(FLASH_COOKIE, value))
+}
+
+/// The message of the jar, if any, and the jar without it.
+pub fn take(config: &SessionConfig, jar: PrivateCookieJar) -> (PrivateCookieJar, Option<Flash>) {
+    let Some(cookie) = jar.get(FLASH_COOKIE) else {
+        return (jar, None);
+    };
+    let flash = match cookie.value().split_once(':') {
+        Some(("success", message)) => Some(Flash::success(message)),
+        Some(("error", message)) => Some(Flash::error(message)),
+        _ => None,
+    };
+    let jar = jar.remove(config.cookie(FLASH_COOKIE, String::new()));
+    (jar, flash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use axum_extra::extract::cookie::{Key, SameSite};
+    use claim::assert_none;
+
+    fn config() -> SessionConfig {
+        SessionConfig {
+            key: Key::generate(),
+            idle_timeout: chrono::Duration::minutes(30),
+            absolute_timeout: chrono::Duration::hours(12),
+            secure_cookie: true,
+        }
+    }
+
+    #[test]
+    fn a_message_is_taken_once() {
+        let config = config();
+        let jar = config.jar(&HeaderMap::new());
+        let jar = set(&config, jar, Flash::error("Title: is required."));
+
+        let (jar, flash) = take(&config, jar);
+        assert_eq!(flash, Some(Flash::error("Title: is required.")));
+        let (_, flash) = take(&config, jar);
+        assert_none!(flash);
+    }
+
+    #[test]
+    fn the_cookie_has_the_attributes_of_the_session_cookie() {
+        let config = config();
+        let jar = set(
+            &config,
+            config.jar(&HeaderMap::new()),
+            Flash::success("Saved."),
+        );
+
+        let cookie = jar.get(FLASH_COOKIE).unwrap();
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(cookie.http_only(), Some(true));
+    }
+}
@@ -0,0 +1,133 @@
+//! The HTML around the content of the pages. Everything that comes from the database
+//! or the request must go through `escape`.
+use axum::http::StatusCode;
+use axum::response::Html;
+
+use crate::authentication::AuthenticatedUser;
+use crate::routes::pages::csrf::CSRF_FIELD;
+use crate::routes::pages::flash::Flash;
+
+const STYLE: &str = "
+body { font-family: sans-serif; max-width: 60rem; margin: 0 auto; padding: 1rem; }
+nav { display: flex; gap: 1rem; align-items: center; border-bottom: 1px solid #ccc; padding-bottom: .5rem; }
+nav form { margin-left: auto; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: .25rem .5rem; border-bottom: 1px solid #eee; }
+label { display: block; margin-top: .5rem; }
+input[type=text], input[type=password], textarea { width: 100%; box-sizing: border-box; }
+.flash { padding: .5rem; margin: 1rem 0; }
+.flash.success { background: #e6f4ea; }
+.flash.error { background: #fce8e6; }
+";
+
+/// Replace the characters that have a meaning in HTML, for text and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The hidden field every POST form needs.
+pub fn csrf_field(csrf_token: &str) -> String {
+    format!(
+        r#"<input type="hidden" name="{}" value="{}">"#,
+        CSRF_FIELD,
+        escape(csrf_token)
+    )
+}
+
+/// A form with only a button, for actions without input.
+pub fn button_form(action: &str, label: &str, csrf_token: &str) -> String {
+    format!(
+        r#"<form method="post" action="{}">{}<button type="submit">{}</button></form>"#,
+        escape(action),
+        csrf_field(csrf_token),
+        escape(label)
+    )
+}
+
+pub fn layout(
+    title: &str,
+    user: Option<&AuthenticatedUser>,
+    flash: Option<&Flash>,
+    csrf_token: &str,
+    body: &str,
+) -> Html<String> {
+    let nav = match user {
+        Some(user) => format!(
+            r#"<nav><a href="/dashboard">Dashboard</a> <a href="/dashboard/subscribers">Subscribers</a> <span>{} ({})</span> {}</nav>"#,
+            escape(&user.username),
+            user.role.as_str(),
+            button_form("/dashboard/logout", "Log out", csrf_token)
+        ),
+        None => String::new(),
+    };
+    let flash = match flash {
+        Some(flash) => format!(
+            r#"<p class="flash {}" role="status">{}</p>"#,
+            flash.level.as_str(),
+            escape(&flash.message)
+        ),
+        None => String::new(),
+    };
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title} - zero2prod</title>
+<style>{STYLE}</style>
+</head>
+<body>
+{nav}
+<main>
+<h1>{title}</h1>
+{flash}
+{body}
+</main>
+</body>
+</html>
+"#,
+        title = escape(title),
+    ))
+}
+
+pub fn error_page(status: StatusCode, message: &str) -> Html<String> {
+    let title = status.canonical_reason().unwrap_or("Error");
+    let body = format!(
+        r#"<p>{}</p><p><a href="/dashboard">Back to the dashboard</a></p>"#,
+        escape(message)
+    );
+    layout(title, None, None, "", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#x27;Jerry&#x27;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn the_flash_message_is_escaped() {
+        let flash = Flash::error("<script>");
+        let Html(page) = layout("Title", None, Some(&flash), "token", "");
+        assert!(page.contains("&lt;script&gt;"));
+        assert!(!page.contains("<script>"));
+    }
+}
@@ -0,0 +1,149 @@
+use axum::extract::{Form, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::extract::cookie::PrivateCookieJar;
+use axum_macros::debug_handler;
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit::AuditContext;
+use crate::authentication::{session, AuthError};
+use crate::models::{CsrfForm, LoginRequest, MfaCodeRequest};
+use crate::routes::login::{authenticate, complete_second_factor};
+use crate::routes::pages::flash::Flash;
+use crate::routes::pages::html::{csrf_field, escape};
+use crate::routes::pages::{csrf, redirect_with, PageContext, PageError, LOGIN_PAGE};
+
+const MFA_PAGE: &str = "/dashboard/login/mfa";
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Showing login page", skip(state, headers))]
+pub async fn login_page(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, PageError> {
+    let page = PageContext::new(&state, &headers);
+    let jar = state.sessions.jar(&headers);
+    if session::get_session_user(&state.pg_pool, &state.sessions, &jar)
+        .await?
+        .is_some()
+    {
+        return Ok(Redirect::to("/dashboard").into_response());
+    }
+    let body = format!(
+        r#"<form method="post" action="{}">
+{}
+<label>Username <input type="text" name="username" autocomplete="username" required autofocus></label>
+<label>Password <input type="password" name="password" autocomplete="current-password" required></label>
+<p><button type="submit">Log in</button></p>
+</form>"#,
+        LOGIN_PAGE,
+        csrf_field(page.csrf_token()),
+    );
+    Ok(page.render("Log in", None, &body).into_response())
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Logging in with the login page",
+    skip(state, headers, form),
+    fields(username = %form.get_payload().get_username(), user_id = tracing::field::Empty)
+)]
+pub async fn login(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    context: AuditContext,
+    Form(form): Form<CsrfForm<LoginRequest>>,
+) -> Result<Response, PageError> {
+    let jar = state.sessions.jar(&headers);
+    csrf::verify(&jar, form.get_csrf_token())?;
+    let credentials = form.into_payload().into_credentials();
+    match authenticate(&state, jar.clone(), &context, credentials).await {
+        Ok((jar, true)) => Ok((jar, Redirect::to(MFA_PAGE)).into_response()),
+        Ok((jar, false)) => Ok((jar, Redirect::to("/dashboard")).into_response()),
+        Err(AuthError::InvalidCredentials) => Ok(redirect_with(
+            &state.sessions,
+            jar,
+            Flash::error("Wrong username or password."),
+            LOGIN_PAGE,
+        )
+        .into_response()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The second step for users with TOTP, after the password was accepted.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Showing second factor page", skip(state, headers))]
+pub async fn login_mfa_page(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, PageError> {
+    let page = PageContext::new(&state, &headers);
+    let jar = state.sessions.jar(&headers);
+    let Some((_, username)) = session::get_mfa_pending_user(&state.pg_pool, &jar).await? else {
+        return Ok(Redirect::to(LOGIN_PAGE).into_response());
+    };
+    let body = format!(
+        r#"<p>Logging in as {}.</p>
+<form method="post" action="{}">
+{}
+<label>Code from your authenticator app, or a recovery code <input type="text" name="code" autocomplete="one-time-code" required autofocus></label>
+<p><button type="submit">Verify</button></p>
+</form>"#,
+        escape(&username),
+        MFA_PAGE,
+        csrf_field(page.csrf_token()),
+    );
+    Ok(page
+        .render("Two-factor authentication", None, &body)
+        .into_response())
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Verifying second factor with the login page",
+    skip(state, headers, form),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn login_mfa(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    context: AuditContext,
+    Form(form): Form<CsrfForm<MfaCodeRequest>>,
+) -> Result<Response, PageError> {
+    let jar = state.sessions.jar(&headers);
+    csrf::verify(&jar, form.get_csrf_token())?;
+    let code = form.get_payload().get_code();
+    match complete_second_factor(&state, jar.clone(), &context, code).await {
+        Ok(jar) => Ok((jar, Redirect::to("/dashboard")).into_response()),
+        // After too many wrong codes the session is gone, and the page sends the
+        // user back to the login.
+        Err(AuthError::InvalidCredentials) => Ok(redirect_with(
+            &state.sessions,
+            jar,
+            Flash::error("The code is not valid."),
+            MFA_PAGE,
+        )
+        .into_response()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Logging out with the dashboard", skip(state, headers, form))]
+pub async fn logout(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    Form(form): Form<CsrfForm<()>>,
+) -> Result<(PrivateCookieJar, Redirect), PageError> {
+    let jar = state.sessions.jar(&headers);
+    csrf::verify(&jar, form.get_csrf_token())?;
+    let jar = session::end_session(&state.pg_pool, jar).await?;
+    Ok(redirect_with(
+        &state.sessions,
+        jar,
+        Flash::success("You have been logged out."),
+        LOGIN_PAGE,
+    ))
+}
@@ -0,0 +1,161 @@
+//! Server-rendered HTML pages of the admin dashboard, for people who do not use the
+//! JSON API. Every form carries a CSRF token, and the result of a form is shown as a
+//! flash message on the page it redirects to.
+pub mod csrf;
+pub mod dashboard;
+pub mod email;
+pub mod flash;
+pub mod html;
+pub mod login;
+pub mod password;
+pub mod subscribers;
+
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum_extra::extract::cookie::PrivateCookieJar;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::app::AppState;
+use crate::authentication::authorization::RequiredPermission;
+use crate::authentication::{AuthError, AuthenticatedUser, Authorized, SessionConfig};
+use crate::routes::admin::AdminError;
+use flash::Flash;
+
+pub const LOGIN_PAGE: &str = "/dashboard/login";
+
+/// Errors of the pages. Unlike `AdminError` they are shown as HTML, and a missing
+/// session redirects to the login page.
+#[derive(thiserror::Error, Debug)]
+pub enum PageError {
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error("invalid or missing CSRF token")]
+    Csrf,
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("sqlx: {0}")]
+    PostgreSQL(#[from] sqlx::Error),
+    #[error("unexpected: {0}")]
+    Unexpected(String),
+}
+
+impl From<AdminError> for PageError {
+    fn from(e: AdminError) -> Self {
+        match e {
+            AdminError::Auth(e) => PageError::Auth(e),
+            AdminError::NotFound(detail) => PageError::NotFound(detail),
+            AdminError::Conflict(detail) | AdminError::Invalid(detail) => {
+                PageError::Invalid(detail)
+            }
+            AdminError::PostgreSQL(e) => PageError::PostgreSQL(e),
+            AdminError::Unexpected(e) => PageError::Unexpected(e),
+        }
+    }
+}
+
+impl IntoResponse for PageError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            PageError::Auth(AuthError::MissingCredentials | AuthError::InvalidCredentials) => {
+                return Redirect::to(LOGIN_PAGE).into_response();
+            }
+            PageError::Auth(AuthError::Forbidden(_)) => (
+                StatusCode::FORBIDDEN,
+                "Your role does not allow this.".to_string(),
+            ),
+            PageError::Csrf => {
+                tracing::warn!("Rejected a form: {}", self);
+                (
+                    StatusCode::FORBIDDEN,
+                    "The form has expired. Go back, reload the page and try again.".to_string(),
+                )
+            }
+            PageError::NotFound(detail) => (StatusCode::NOT_FOUND, detail),
+            PageError::Invalid(detail) => (StatusCode::UNPROCESSABLE_ENTITY, detail),
+            PageError::Auth(_) | PageError::PostgreSQL(_) | PageError::Unexpected(_) => {
+                tracing::error!("Failed to render a page: {}", self);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong, please try again later.".to_string(),
+                )
+            }
+        };
+        (status, html::error_page(status, &message)).into_response()
+    }
+}
+
+/// `Authorized<P>` for pages: without a session the user is sent to the login page.
+pub struct PageAuth<P> {
+    pub user: AuthenticatedUser,
+    permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<P> FromRequestParts<Arc<AppState>> for PageAuth<P>
+where
+    P: RequiredPermission + Send,
+{
+    type Rejection = PageError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let auth = Authorized::<P>::from_request_parts(parts, state).await?;
+        Ok(Self {
+            user: auth.user,
+            permission: PhantomData,
+        })
+    }
+}
+
+/// The cookies a page is rendered with: the flash message left by the previous
+/// request, which is removed once shown, and the CSRF token for its forms.
+pub struct PageContext {
+    jar: PrivateCookieJar,
+    flash: Option<Flash>,
+    csrf_token: String,
+}
+
+impl PageContext {
+    pub fn new(state: &AppState, headers: &HeaderMap) -> Self {
+        let jar = state.sessions.jar(headers);
+        let (jar, flash) = flash::take(&state.sessions, jar);
+        let (jar, csrf_token) = csrf::token(&state.sessions, jar);
+        Self {
+            jar,
+            flash,
+            csrf_token,
+        }
+    }
+
+    pub fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+
+    pub fn render(
+        self,
+        title: &str,
+        user: Option<&AuthenticatedUser>,
+        body: &str,
+    ) -> (PrivateCookieJar, Html<String>) {
+        let page = html::layout(title, user, self.flash.as_ref(), &self.csrf_token, body);
+        (self.jar, page)
+    }
+}
+
+/// Redirect after a form, with a message for the next page.
+pub fn redirect_with(
+    config: &SessionConfig,
+    jar: PrivateCookieJar,
+    flash: Flash,
+    to: &str,
+) -> (PrivateCookieJar, Redirect) {
+    (flash::set(config, jar, flash), Redirect::to(to))
+}
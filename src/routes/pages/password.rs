@@ -0,0 +1,64 @@
+use axum::extract::{Form, Query, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum_macros::debug_handler;
+use std::sync::Arc;
+
+use crate::app;
+use crate::audit::AuditContext;
+use crate::authentication::AuthError;
+use crate::models::{CsrfForm, ResetPasswordRequest, TokenQuery};
+use crate::routes::pages::flash::Flash;
+use crate::routes::pages::html::{csrf_field, escape};
+use crate::routes::pages::{csrf, redirect_with, PageContext, PageError, LOGIN_PAGE};
+use crate::routes::password::reset_with_token;
+
+const RESET_FORM: &str = "/dashboard/password/reset";
+
+/// The page the reset email links to. The token is checked when the form is sent.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Showing password reset page", skip_all)]
+pub async fn reset_password_page(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<TokenQuery>,
+) -> Response {
+    let page = PageContext::new(&state, &headers);
+    let body = format!(
+        r#"<form method="post" action="{}">
+{}
+<input type="hidden" name="token" value="{}">
+<label>New password <input type="password" name="new_password" autocomplete="new-password" required autofocus></label>
+<p><button type="submit">Set password</button></p>
+</form>"#,
+        RESET_FORM,
+        csrf_field(page.csrf_token()),
+        escape(query.get_token()),
+    );
+    page.render("Choose a new password", None, &body)
+        .into_response()
+}
+
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Resetting a password with the reset page",
+    skip(state, headers, form),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn reset_password(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    context: AuditContext,
+    Form(form): Form<CsrfForm<ResetPasswordRequest>>,
+) -> Result<Response, PageError> {
+    let jar = state.sessions.jar(&headers);
+    csrf::verify(&jar, form.get_csrf_token())?;
+    let flash = match reset_with_token(&state, &context, form.get_payload()).await {
+        Ok(()) => Flash::success("Your password has been changed, log in with the new one."),
+        Err(AuthError::InvalidCredentials) => {
+            Flash::error("The reset link is invalid, used or expired. Ask for a new one.")
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(redirect_with(&state.sessions, jar, flash, LOGIN_PAGE).into_response())
+}
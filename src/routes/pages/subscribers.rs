@@ -0,0 +1,89 @@
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::Html;
+use axum_extra::extract::cookie::PrivateCookieJar;
+use axum_macros::debug_handler;
+use std::sync::Arc;
+
+use crate::app;
+use crate::authentication::permissions;
+use crate::models::validation::SUBSCRIPTION_STATUSES;
+use crate::models::SubscriberListQuery;
+use crate::routes::admin::subscribers::list_page;
+use crate::routes::pages::html::escape;
+use crate::routes::pages::{PageAuth, PageContext, PageError};
+
+/// The subscribers, newest first, filtered by status and searched by email or name.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Showing subscribers page", skip(state, headers, auth), fields(user_id = %auth.user.user_id))]
+pub async fn subscribers_page(
+    State(state): State<Arc<app::AppState>>,
+    headers: HeaderMap,
+    auth: PageAuth<permissions::ListSubscribers>,
+    Query(query): Query<SubscriberListQuery>,
+) -> Result<(PrivateCookieJar, Html<String>), PageError> {
+    let page = PageContext::new(&state, &headers);
+    let (subscribers, next_cursor) = list_page(&state.pg_pool, &query).await?;
+
+    let status_options: String = std::iter::once("")
+        .chain(SUBSCRIPTION_STATUSES.iter().copied())
+        .map(|status| {
+            format!(
+                r#"<option value="{}"{}>{}</option>"#,
+                status,
+                if query.get_status().unwrap_or("") == status {
+                    " selected"
+                } else {
+                    ""
+                },
+                if status.is_empty() { "any" } else { status },
+            )
+        })
+        .collect();
+    let rows: String = subscribers
+        .iter()
+        .map(|subscriber| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&subscriber.email),
+                escape(&subscriber.name),
+                escape(&subscriber.status),
+                subscriber.subscribed_at.format("%Y-%m-%d %H:%M"),
+            )
+        })
+        .collect();
+    let next_page = match next_cursor {
+        Some(cursor) => {
+            let mut link = url::form_urlencoded::Serializer::new(String::new());
+            if let Some(status) = query.get_status() {
+                link.append_pair("status", status);
+            }
+            if let Some(search) = query.get_search() {
+                link.append_pair("q", search);
+            }
+            link.append_pair("cursor", &cursor);
+            format!(
+                r#"<p><a href="/dashboard/subscribers?{}">Next page</a></p>"#,
+                escape(&link.finish())
+            )
+        }
+        None => String::new(),
+    };
+    let body = format!(
+        r#"<form method="get" action="/dashboard/subscribers">
+<label>Status <select name="status">{}</select></label>
+<label>Email or name <input type="text" name="q" value="{}"></label>
+<p><button type="submit">Filter</button></p>
+</form>
+<table>
+<thead><tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed</th></tr></thead>
+<tbody>{}</tbody>
+</table>
+{}"#,
+        status_options,
+        escape(query.get_search().unwrap_or("")),
+        rows,
+        next_page,
+    );
+    Ok(page.render("Subscribers", Some(&auth.user), &body))
+}
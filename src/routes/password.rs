@@ -0,0 +1,147 @@
+use axum::extract::{Json, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use tracing::Instrument;
+
+use crate::app;
+use crate::audit::{self, Actor, AuditContext, AuditEvent};
+use crate::authentication::password_reset::{self, PasswordReset};
+use crate::authentication::{hash_in_background, AuthError};
+use crate::email_client::{EmailClient, ValidEmail};
+use crate::models;
+use crate::redact;
+
+/// Email a reset link to the admin with this address. The response is the same
+/// whether or not the address is known, or the link could be stored, and the email
+/// is sent in the background so the response time does not tell either.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(name = "Requesting a password reset", skip(state, payload))]
+pub async fn forgot_password(
+    State(state): State<Arc<app::AppState>>,
+    context: AuditContext,
+    Json(payload): Json<models::EmailRequest>,
+) -> StatusCode {
+    let reset = match store_reset_token(&state, &context, payload.get_email()).await {
+        Ok(Some(reset)) => reset,
+        Ok(None) => {
+            tracing::info!("No admin with this email, not sending a reset link.");
+            return StatusCode::OK;
+        }
+        Err(e) => {
+            tracing::error!("Failed to store a reset link: {}", redact::DbError(&e));
+            return StatusCode::OK;
+        }
+    };
+    let email_client = state.email_client.clone();
+    let base_url = state.base_url.0.clone();
+    tokio::spawn(
+        async move { send_reset_email(&email_client, &base_url, &reset).await }
+            .instrument(tracing::Span::current()),
+    );
+    StatusCode::OK
+}
+
+// The token and its audit entry, both or neither.
+async fn store_reset_token(
+    state: &app::AppState,
+    context: &AuditContext,
+    email: &str,
+) -> Result<Option<PasswordReset>, sqlx::Error> {
+    let mut transaction = state.pg_pool.begin().await?;
+    let Some(reset) = password_reset::create_reset_token(&mut transaction, email).await? else {
+        return Ok(None);
+    };
+    audit::record(
+        &mut *transaction,
+        context,
+        &Actor::user(reset.user_id, &reset.username),
+        AuditEvent::new("password.reset_requested").target("user", reset.user_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    Ok(Some(reset))
+}
+
+/// Set a new password with the token from a reset link. Logs the admin out everywhere.
+#[debug_handler(state = Arc<app::AppState>)]
+#[tracing::instrument(
+    name = "Resetting a password",
+    skip(state, payload),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn reset_password(
+    State(state): State<Arc<app::AppState>>,
+    context: AuditContext,
+    Json(payload): Json<models::ResetPasswordRequest>,
+) -> Result<StatusCode, AuthError> {
+    reset_with_token(&state, &context, &payload).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Reset the password and audit it, for the JSON endpoint and the reset page.
+/// An unknown, used or expired token is `InvalidCredentials`.
+pub async fn reset_with_token(
+    state: &app::AppState,
+    context: &AuditContext,
+    request: &models::ResetPasswordRequest,
+) -> Result<(), AuthError> {
+    // Hash before taking any locks, it takes a while.
+    let password_hash = hash_in_background(request.get_new_password().clone()).await?;
+    let mut transaction = state.pg_pool.begin().await?;
+    let (user_id, username) =
+        password_reset::reset_password(&mut transaction, request.get_token(), &password_hash)
+            .await?
+            .ok_or(AuthError::InvalidCredentials)?;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    audit::record(
+        &mut *transaction,
+        context,
+        &Actor::user(user_id, &username),
+        AuditEvent::new("password.reset").target("user", user_id),
+    )
+    .await?;
+    transaction.commit().await?;
+    tracing::info!("Password reset.");
+    Ok(())
+}
+
+async fn send_reset_email(email_client: &EmailClient, base_url: &str, reset: &PasswordReset) {
+    let recipient = match ValidEmail::new(&reset.email) {
+        Ok(recipient) => recipient,
+        Err(e) => {
+            tracing::error!(user_id = %reset.user_id, "Stored email is not valid: {}", e);
+            return;
+        }
+    };
+    let link = format!(
+        "{}/password/reset?token={}",
+        base_url,
+        reset.token.expose_secret()
+    );
+    let result = email_client
+        .send_email(
+            &recipient,
+            "Reset your password",
+            &format!(
+                "Click <a href=\"{}\">here</a> to choose a new password.<br />\
+                The link works once, within an hour. If you did not ask for it, ignore this email.",
+                link
+            ),
+            &format!(
+                "Visit {} to choose a new password.\n\
+                The link works once, within an hour. If you did not ask for it, ignore this email.",
+                link
+            ),
+        )
+        .await;
+    match result {
+        Ok(_) => tracing::info!(user_id = %reset.user_id, "Password reset email sent."),
+        Err(e) => tracing::error!(
+            user_id = %reset.user_id,
+            "Failed to send password reset email: {:?}",
+            e
+        ),
+    }
+}
@@ -7,22 +7,19 @@ use uuid::Uuid;
 use std::sync::Arc;
 use sqlx::{Postgres, Transaction};
 
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
-
 use crate::app;
 use crate::models;
 use crate::email_client::{ValidEmail, EmailClient};
+use crate::token::generate_token;
+use crate::redact;
 
 #[debug_handler]
 #[tracing::instrument(
     name = "Adding a new subscriber",
     skip(state, payload),
     fields(
-        // could not find a way to get this automatically with tower_http like in the book.
-        request_id = %Uuid::new_v4(), 
-        subscriber_email = %payload.get_email(),
-        subscriber_name= %payload.get_name()
+        subscriber_email = %redact::Email(payload.get_email()),
+        subscriber_name = %redact::Name(payload.get_name())
     )
 )]
 pub async fn subscribe(
@@ -50,20 +47,20 @@ pub async fn subscribe(
                 subscriber_id = id;
             },
             Err(err) => {
-                tracing::error!("Insertion failed with error: {:?}", err);
+                tracing::error!("Insertion failed with error: {}", redact::DbError(&err));
                 return StatusCode::BAD_REQUEST
             }
         }
     
     // generate the token and store it in the db
-    let token = generate_subscription_token();
+    let token = generate_token();
     let response_store_token = store_token(&mut transaction, subscriber_id, &token).await;
     match response_store_token {
         Ok(_) => {
             tracing::info!("Token insertion succeeded.");
         },
         Err(err) => {
-            tracing::error!("Insertion failed with error: {:?}", err);
+            tracing::error!("Insertion failed with error: {}", redact::DbError(&err));
             return StatusCode::BAD_REQUEST
         }
     }
@@ -71,6 +68,7 @@ pub async fn subscribe(
     if transaction.commit().await.is_err() {
         return StatusCode::INTERNAL_SERVER_ERROR;
     }
+    metrics::increment_counter!("subscriptions_created_total");
     // directly return the statuscode returned by send_confirmation_email.
     send_confirmation_email(&state.base_url, &state.email_client, payload.get_email(), &token).await
 
@@ -78,7 +76,7 @@ pub async fn subscribe(
 
 #[tracing::instrument(
     name = "Insert subscriber in the database",
-    skip(transaction)
+    skip(transaction, email, name)
 )]
 async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>, 
@@ -101,24 +99,14 @@ async fn insert_subscriber(
     // that implements the executor trait.
     .execute(&mut **transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
+    .inspect_err(|e| {
+        tracing::error!("Failed to execute query: {}", redact::DbError(e));
     })?;
 
     Ok(subscriber_id)
     
 }
 
-/// Generate a random 25-characters-long case-sensitive subscription token.
-fn generate_subscription_token() -> String {
-    let mut rng = thread_rng();
-    std::iter::repeat_with(|| rng.sample(Alphanumeric))
-        .map(char::from)
-        .take(25)
-        .collect()
-}
-
 #[tracing::instrument(
     name = "Store subscription token in the database",
     skip(subscription_token, transaction)
@@ -138,15 +126,14 @@ pub async fn store_token(
     // that implements the executor trait.
     .execute(&mut **transaction)
     .await
-    .map_err(|e| {
-    tracing::error!("Failed to execute query: {:?}", e);
-    e
+    .inspect_err(|e| {
+    tracing::error!("Failed to execute query: {}", redact::DbError(e));
     })?;
     Ok(())
     }
 
 
-async fn send_confirmation_email(base_url: &app::ApplicationBaseUrl, email_client: &EmailClient, email_address: &str, token: &str) -> StatusCode {
+pub async fn send_confirmation_email(base_url: &app::ApplicationBaseUrl, email_client: &EmailClient, email_address: &str, token: &str) -> StatusCode {
 // Send a confirmation email:
     
     // The validation is superfluous, since the validity is also checked
@@ -0,0 +1,189 @@
+//! Graceful shutdown. On SIGTERM or SIGINT the server stops accepting connections
+//! and lets in-flight requests finish, then the background jobs finish the job they
+//! are running, each within the deadline of `application.shutdown_timeout_seconds`.
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use std::net::{SocketAddr, TcpListener};
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinSet;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Cancel `token` when the process is asked to stop, with SIGTERM (e.g. by the
+/// orchestrator) or SIGINT (ctrl-c).
+pub fn cancel_on_signal(token: CancellationToken) -> Result<(), String> {
+    // Listen right away, so a signal during startup is not missed.
+    let mut terminate = signal(SignalKind::terminate()).map_err(|e| e.to_string())?;
+    let mut interrupt = signal(SignalKind::interrupt()).map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = terminate.recv() => tracing::info!("Received SIGTERM, shutting down."),
+            _ = interrupt.recv() => tracing::info!("Received SIGINT, shutting down."),
+        }
+        token.cancel();
+    });
+    Ok(())
+}
+
+/// Serve `app` on `listener`, over HTTPS if there is a `tls` config, until `shutdown`
+/// is cancelled. Then wait up to `deadline` for the in-flight requests; connections
+/// still open after that are closed.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tls: Option<RustlsConfig>,
+    shutdown: CancellationToken,
+    deadline: Duration,
+) -> Result<(), String> {
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to use the listener: {}", e))?;
+    let handle = Handle::new();
+    let server_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.cancelled().await;
+        server_handle.graceful_shutdown(Some(deadline));
+        tokio::time::sleep(deadline).await;
+        if server_handle.connection_count() > 0 {
+            tracing::warn!(
+                "In-flight requests did not finish within {:?}, dropping them.",
+                deadline
+            );
+        }
+    });
+
+    // ConnectInfo gives handlers the client address, e.g. for the audit log.
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let result = match tls {
+        Some(config) => {
+            axum_server::from_tcp_rustls(listener, config)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+        None => {
+            axum_server::from_tcp(listener)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+    };
+    result.map_err(|e| format!("Server error: {}", e))
+}
+
+/// Wait for the background jobs, which stop after their current run once their token
+/// is cancelled. Jobs still running after `deadline` are aborted.
+pub async fn finish_jobs(mut jobs: JoinSet<()>, deadline: Duration) {
+    let all_done = async { while jobs.join_next().await.is_some() {} };
+    if tokio::time::timeout(deadline, all_done).await.is_err() {
+        tracing::warn!(
+            "{} background job(s) did not finish within {:?}, aborting them.",
+            jobs.len(),
+            deadline
+        );
+        jobs.shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn slow_app(delay: Duration) -> Router {
+        Router::new().route(
+            "/slow",
+            get(move || async move {
+                tokio::time::sleep(delay).await;
+                "done"
+            }),
+        )
+    }
+
+    async fn start(
+        delay: Duration,
+        deadline: Duration,
+    ) -> (
+        String,
+        CancellationToken,
+        tokio::task::JoinHandle<Result<(), String>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/slow", listener.local_addr().unwrap());
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(serve(
+            listener,
+            slow_app(delay),
+            None,
+            shutdown.clone(),
+            deadline,
+        ));
+        (url, shutdown, server)
+    }
+
+    #[tokio::test]
+    async fn in_flight_requests_finish_after_shutdown() {
+        let (url, shutdown, server) =
+            start(Duration::from_millis(300), Duration::from_secs(5)).await;
+
+        let request = tokio::spawn(reqwest::get(url.clone()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown.cancel();
+
+        let response = request.await.unwrap().unwrap();
+        assert_eq!(response.text().await.unwrap(), "done");
+        server.await.unwrap().unwrap();
+        // No new connections are accepted.
+        assert!(reqwest::get(url).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn requests_are_dropped_after_the_deadline() {
+        let (url, shutdown, server) =
+            start(Duration::from_secs(30), Duration::from_millis(200)).await;
+
+        let request = tokio::spawn(reqwest::get(url));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let start = Instant::now();
+        shutdown.cancel();
+
+        server.await.unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        request.abort();
+    }
+
+    #[tokio::test]
+    async fn jobs_finish_their_current_run() {
+        let shutdown = CancellationToken::new();
+        let finished = Arc::new(AtomicBool::new(false));
+        let mut jobs = JoinSet::new();
+        let (token, flag) = (shutdown.clone(), finished.clone());
+        jobs.spawn(async move {
+            token.cancelled().await;
+            // The run that was going on when the token was cancelled.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        shutdown.cancel();
+        finish_jobs(jobs, Duration::from_secs(5)).await;
+
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn stuck_jobs_are_aborted_after_the_deadline() {
+        let mut jobs = JoinSet::new();
+        jobs.spawn(std::future::pending::<()>());
+
+        let start = Instant::now();
+        finish_jobs(jobs, Duration::from_millis(100)).await;
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,23 +1,157 @@
+use axum::http::HeaderMap;
+use once_cell::sync::OnceCell;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self as sdktrace, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::WithExportConfig;
 use tracing::subscriber::set_global_default;
-use tracing::Subscriber;
+use tracing::{Span, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::fmt::{self, writer::BoxMakeWriter};
+use tracing_subscriber::layer::{Layer, Layered, SubscriberExt};
+use tracing_subscriber::{reload, EnvFilter, Registry};
 
-pub fn get_subscriber(name: String, env_filter: String) -> impl Subscriber + Send + Sync {
+use crate::configuration::TelemetrySettings;
+
+/// Swaps the `EnvFilter` of the subscriber it was made with, see `get_subscriber`.
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+// Handle to swap the `EnvFilter` of the global subscriber.
+static LOG_FILTER: OnceCell<LogFilterHandle> = OnceCell::new();
+
+/// How log lines are written.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Bunyan JSON lines, for log collectors.
+    #[default]
+    Json,
+    /// Multi-line and colored, for reading in a terminal.
+    Pretty,
+    /// One short line per event.
+    Compact,
+}
+
+/// Where log lines are written.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogWriter {
+    #[default]
+    Stdout,
+    Stderr,
+    /// Nowhere, e.g. in tests. Spans are still exported.
+    Discard,
+}
+
+impl LogWriter {
+    fn make_writer(self) -> BoxMakeWriter {
+        match self {
+            LogWriter::Stdout => BoxMakeWriter::new(std::io::stdout),
+            LogWriter::Stderr => BoxMakeWriter::new(std::io::stderr),
+            LogWriter::Discard => BoxMakeWriter::new(std::io::sink),
+        }
+    }
+}
+
+type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+
+/// A subscriber that writes log lines in `format` to `writer`, and hands the spans to
+/// `tracer` if there is one, see `otlp_tracer`. Comes with the handle to change its
+/// log filter, for `init_subscriber`.
+pub fn get_subscriber(
+    name: String,
+    env_filter: String,
+    format: LogFormat,
+    writer: LogWriter,
+    tracer: Option<Tracer>,
+) -> (impl Subscriber + Send + Sync, LogFilterHandle) {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, std::io::stdout);
-    Registry::default()
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+
+    let writer = writer.make_writer();
+    let formatting_layer: Box<dyn Layer<FilteredRegistry> + Send + Sync> = match format {
+        LogFormat::Json => {
+            Box::new(JsonStorageLayer.and_then(BunyanFormattingLayer::new(name, writer)))
+        }
+        LogFormat::Pretty => Box::new(fmt::layer().pretty().with_writer(writer)),
+        LogFormat::Compact => Box::new(fmt::layer().compact().with_writer(writer)),
+    };
+    let subscriber = Registry::default()
         .with(env_filter)
-        .with(JsonStorageLayer)
         .with(formatting_layer)
+        .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)));
+    (subscriber, handle)
 }
 
-/// Register a subscriber as global default to process span data.
+/// A tracer that exports spans in batches to the OTLP/HTTP endpoint of the settings,
+/// or `None` if no endpoint is configured. It must be created inside the Tokio runtime.
+pub fn otlp_tracer(settings: &TelemetrySettings) -> Result<Option<Tracer>, String> {
+    let Some(endpoint) = &settings.otlp_endpoint else {
+        return Ok(None);
+    };
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(endpoint)
+        .with_timeout(settings.export_timeout());
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(
+            sdktrace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                settings.service_name.clone(),
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)
+        .map(Some)
+        .map_err(|e| format!("Failed to set up the OTLP exporter: {}", e))
+}
+
+/// Export the spans that are still buffered, before the process exits.
+pub fn shutdown_tracer() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// The trace context of a W3C `traceparent` (and `tracestate`) header, to continue the
+/// trace of the caller. Empty if there is no valid header.
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Add the `traceparent` (and `tracestate`) header of `span` to an outgoing request,
+/// so the callee joins the trace. Nothing is added if spans are not exported.
+pub fn inject_context(span: &Span, headers: &mut HeaderMap) {
+    TraceContextPropagator::new().inject_context(&span.context(), &mut HeaderInjector(headers));
+}
+
+/// Register a subscriber as global default to process span data, and its log filter
+/// as the one `set_log_filter` changes.
 ///
 /// It should only be called once!
-pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
+pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync, log_filter: LogFilterHandle) {
     LogTracer::init().expect("Failed to set logger"); // converts regular log messages to trace.
     set_global_default(subscriber).expect("Failed to set subscriber");
+    let _ = LOG_FILTER.set(log_filter);
+}
+
+/// Replace the log filter of the running subscriber, e.g. with `debug` or `info,sqlx=warn`.
+pub fn set_log_filter(directive: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directive).map_err(|e| e.to_string())?;
+    let handle = LOG_FILTER
+        .get()
+        .ok_or_else(|| "The log filter cannot be changed for this subscriber.".to_string())?;
+    handle.reload(filter).map_err(|e| e.to_string())
+}
+
+/// The directive of the running subscriber's log filter, if it can be changed.
+pub fn log_filter() -> Option<String> {
+    LOG_FILTER
+        .get()
+        .and_then(|handle| handle.with_current(|filter| filter.to_string()).ok())
 }
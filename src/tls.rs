@@ -0,0 +1,100 @@
+//! HTTPS without a reverse proxy: the certificate and key are read from the files in
+//! `application.tls`, and read again whenever they change, so a rotated certificate
+//! is used for new connections without a restart. Plain HTTP requests can be sent
+//! to HTTPS by a second listener.
+use axum::extract::{Host, State};
+use axum::http::uri::{Authority, PathAndQuery};
+use axum::http::{StatusCode, Uri};
+use axum::response::Redirect;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use notify::{RecursiveMode, Watcher};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::configuration::TlsSettings;
+
+/// The certificate chain and key of the settings, both PEM encoded.
+pub async fn load(settings: &TlsSettings) -> Result<RustlsConfig, String> {
+    RustlsConfig::from_pem_file(&settings.cert_path, &settings.key_path)
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to load the TLS certificate {} and key {}: {}",
+                settings.cert_path.display(),
+                settings.key_path.display(),
+                e
+            )
+        })
+}
+
+/// Reload `config` when the certificate or key files change. A pair that cannot be
+/// loaded, e.g. because only one of the files is written yet, keeps the previous one.
+pub fn spawn_reloader(config: RustlsConfig, settings: TlsSettings) -> Result<(), String> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if matches!(event, Ok(ref e) if !e.kind.is_access()) {
+            let _ = tx.send(());
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    // The directories, because certificates are usually replaced rather than edited,
+    // e.g. by renaming a new file or swapping a symlink.
+    for path in [&settings.cert_path, &settings.key_path] {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+    }
+
+    tokio::spawn(async move {
+        // Keep the watcher alive for as long as this task runs.
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            // The certificate and key are written one after the other, wait for both.
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            while rx.try_recv().is_ok() {}
+
+            match config
+                .reload_from_pem_file(&settings.cert_path, &settings.key_path)
+                .await
+            {
+                Ok(()) => tracing::info!("Reloaded the TLS certificate."),
+                Err(e) => tracing::error!(
+                    "Failed to reload the TLS certificate, keeping the previous one: {}",
+                    e
+                ),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// An app that answers every request with a permanent redirect to the same URL on
+/// HTTPS, served on `https_port`.
+pub fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(redirect).with_state(https_port)
+}
+
+async fn redirect(
+    State(https_port): State<u16>,
+    Host(host): Host,
+    uri: Uri,
+) -> Result<Redirect, StatusCode> {
+    let host = host
+        .parse::<Authority>()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let path = uri
+        .path_and_query()
+        .map(PathAndQuery::as_str)
+        .unwrap_or("/");
+    let location = match https_port {
+        443 => format!("https://{}{}", host.host(), path),
+        port => format!("https://{}:{}{}", host.host(), port, path),
+    };
+    Ok(Redirect::permanent(&location))
+}
@@ -0,0 +1,101 @@
+//! Background jobs. They run next to the HTTP server, or on their own with
+//! `zero2prod worker` so the server instances can be started with `--no-jobs`.
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::app::AppState;
+use crate::authentication::session;
+use crate::rate_limit;
+use crate::shutdown::CancellationToken;
+
+const SESSION_CLEANUP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const RATE_LIMIT_CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often a process running the jobs records that it is alive.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// A worker whose last heartbeat is older than this is considered down.
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(3 * 30);
+
+/// Start all background jobs on the runtime. They run until `shutdown` is cancelled,
+/// see `shutdown::finish_jobs` to wait for them.
+pub fn spawn_jobs(state: &AppState, shutdown: CancellationToken) -> JoinSet<()> {
+    tracing::info!("Starting background jobs.");
+    let mut jobs = JoinSet::new();
+    session::spawn_cleanup_task(
+        &mut jobs,
+        state.pg_pool.clone(),
+        state.sessions.clone(),
+        SESSION_CLEANUP_INTERVAL,
+        shutdown.clone(),
+    );
+    rate_limit::spawn_cleanup_task(
+        &mut jobs,
+        state.pg_pool.clone(),
+        RATE_LIMIT_CLEANUP_INTERVAL,
+        shutdown.clone(),
+    );
+    spawn_heartbeat(
+        &mut jobs,
+        state.pg_pool.clone(),
+        HEARTBEAT_INTERVAL,
+        shutdown,
+    );
+    jobs
+}
+
+fn spawn_heartbeat(
+    jobs: &mut JoinSet<()>,
+    pool: PgPool,
+    every: Duration,
+    shutdown: CancellationToken,
+) {
+    let worker_id = Uuid::new_v4();
+    let started_at = Utc::now();
+    jobs.spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            if let Err(e) = record_heartbeat(&pool, worker_id, started_at).await {
+                tracing::error!("Failed to record the worker heartbeat: {:?}", e);
+            }
+        }
+    });
+}
+
+/// Record that the worker `worker_id` is alive, and forget workers gone for a day.
+#[tracing::instrument(name = "Record worker heartbeat", skip(pool), level = "debug")]
+pub async fn record_heartbeat(
+    pool: &PgPool,
+    worker_id: Uuid,
+    started_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO worker_heartbeats (worker_id, started_at, beat_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (worker_id) DO UPDATE SET beat_at = now()
+        "#,
+        worker_id,
+        started_at,
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(r#"DELETE FROM worker_heartbeats WHERE beat_at < now() - interval '1 day'"#)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// When any worker last recorded a heartbeat, if one ever did.
+#[tracing::instrument(name = "Get last worker heartbeat", skip(pool))]
+pub async fn last_heartbeat(pool: &PgPool) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT max(beat_at) FROM worker_heartbeats"#)
+        .fetch_one(pool)
+        .await
+}
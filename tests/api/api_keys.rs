@@ -0,0 +1,264 @@
+use crate::test_utils;
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use chrono::Utc;
+use serde_json::{json, Value};
+
+impl test_utils::TestSetup {
+    pub async fn post_api_key(&self, cookie: &str, body: &Value) -> TestResponse {
+        self.client
+            .post("/admin/api_keys")
+            .header("Cookie", cookie)
+            .json(body)
+            .send()
+            .await
+    }
+
+    /// Create an API key as the test user and return the key.
+    pub async fn create_api_key(&self, scopes: &[&str]) -> (String, String) {
+        let cookie = self.login_test_user().await;
+        let response = self
+            .post_api_key(&cookie, &json!({"name": "CMS", "scopes": scopes}))
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body: Value = response.json().await;
+        (
+            body["api_key_id"].as_str().unwrap().to_string(),
+            body["key"].as_str().unwrap().to_string(),
+        )
+    }
+
+    pub async fn get_api_me(&self, key: &str) -> TestResponse {
+        self.get_api(key, "/api/me").await
+    }
+
+    pub async fn get_api(&self, key: &str, path: &str) -> TestResponse {
+        self.client
+            .get(path)
+            .header("Authorization", format!("Bearer {}", key))
+            .send()
+            .await
+    }
+
+    pub async fn post_api(&self, key: &str, path: &str) -> TestResponse {
+        self.client
+            .post(path)
+            .header("Authorization", format!("Bearer {}", key))
+            .send()
+            .await
+    }
+}
+
+#[tokio::test]
+pub async fn api_keys_can_only_be_created_by_admins() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup
+        .client
+        .post("/admin/api_keys")
+        .json(&json!({"name": "CMS", "scopes": ["subscribers:read"]}))
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn api_keys_are_stored_hashed() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let (_, key) = test_setup.create_api_key(&["subscribers:read"]).await;
+
+    let stored = sqlx::query!("SELECT prefix, key_hash FROM api_keys")
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.key_hash, key);
+    assert!(!stored.key_hash.contains(&key));
+    assert!(key.starts_with(&stored.prefix));
+}
+
+#[tokio::test]
+pub async fn api_key_authenticates_requests_with_its_scopes() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (api_key_id, key) = test_setup
+        .create_api_key(&["subscribers:read", "subscribers:write"])
+        .await;
+
+    let response = test_setup.get_api_me(&key).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["api_key_id"], api_key_id.as_str());
+    assert_eq!(
+        body["scopes"],
+        json!(["subscribers:read", "subscribers:write"])
+    );
+}
+
+#[tokio::test]
+pub async fn api_key_use_is_recorded() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (_, key) = test_setup.create_api_key(&["subscribers:read"]).await;
+    let cookie = test_setup.login_test_user().await;
+
+    let listing: Value = test_setup
+        .client
+        .get("/admin/api_keys")
+        .header("Cookie", &cookie)
+        .send()
+        .await
+        .json()
+        .await;
+    assert!(listing[0]["last_used_at"].is_null());
+    assert!(listing[0].get("key_hash").is_none());
+
+    test_setup.get_api_me(&key).await;
+
+    let listing: Value = test_setup
+        .client
+        .get("/admin/api_keys")
+        .header("Cookie", &cookie)
+        .send()
+        .await
+        .json()
+        .await;
+    assert!(listing[0]["last_used_at"].is_string());
+}
+
+#[tokio::test]
+pub async fn revoked_api_keys_are_rejected() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (api_key_id, key) = test_setup.create_api_key(&["subscribers:read"]).await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .client
+        .delete(&format!("/admin/api_keys/{}", api_key_id))
+        .header("Cookie", &cookie)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = test_setup.get_api_me(&key).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+}
+
+#[tokio::test]
+pub async fn unknown_or_missing_api_keys_are_rejected() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup.get_api_me("z2p_notarealkey").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = test_setup.client.get("/api/me").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn api_keys_need_known_scopes() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    for scopes in [json!([]), json!(["subscribers:delete"])] {
+        let response = test_setup
+            .post_api_key(&cookie, &json!({"name": "CMS", "scopes": scopes}))
+            .await;
+        assert_eq!(
+            response.status(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "The API accepted scopes {}",
+            scopes
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn subscribers_can_be_read_with_the_read_scope_only() {
+    let test_setup = test_utils::create_test_setup().await;
+    let subscriber_id = test_setup
+        .insert_subscriber("ursula@example.com", "Ursula", "confirmed", Utc::now())
+        .await;
+    let subscriber_path = format!("/api/subscribers/{}", subscriber_id);
+    let (_, reader) = test_setup.create_api_key(&["subscribers:read"]).await;
+    let (_, writer) = test_setup.create_api_key(&["subscribers:write"]).await;
+
+    let response = test_setup.get_api(&reader, "/api/subscribers").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["subscribers"][0]["email"], "ursula@example.com");
+    let response = test_setup.get_api(&reader, &subscriber_path).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for path in ["/api/subscribers", subscriber_path.as_str()] {
+        let response = test_setup.get_api(&writer, path).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", path);
+        let problem: Value = response.json().await;
+        assert!(problem["detail"]
+            .as_str()
+            .unwrap()
+            .contains("subscribers:read"));
+    }
+    let response = test_setup.client.get("/api/subscribers").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn subscribers_can_be_changed_with_the_write_scope_only() {
+    let test_setup = test_utils::create_test_setup().await;
+    let subscriber_id = test_setup
+        .insert_subscriber("ursula@example.com", "Ursula", "confirmed", Utc::now())
+        .await;
+    let subscriber_path = format!("/api/subscribers/{}", subscriber_id);
+    let unsubscribe_path = format!("{}/unsubscribe", subscriber_path);
+    let (_, reader) = test_setup.create_api_key(&["subscribers:read"]).await;
+    let (api_key_id, writer) = test_setup.create_api_key(&["subscribers:write"]).await;
+
+    let response = test_setup
+        .client
+        .patch(&subscriber_path)
+        .header("Authorization", format!("Bearer {}", reader))
+        .json(&json!({ "name": "Ursula K. Le Guin" }))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = test_setup.post_api(&reader, &unsubscribe_path).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let subscriber = sqlx::query!(
+        "SELECT name, status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&test_setup.pg_pool)
+    .await
+    .unwrap();
+    assert_eq!(subscriber.name, "Ursula");
+    assert_eq!(subscriber.status, "confirmed");
+
+    let response = test_setup
+        .client
+        .patch(&subscriber_path)
+        .header("Authorization", format!("Bearer {}", writer))
+        .json(&json!({ "name": "Ursula K. Le Guin" }))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["name"], "Ursula K. Le Guin");
+    let response = test_setup.post_api(&writer, &unsubscribe_path).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["status"], "unsubscribed");
+
+    let actors = sqlx::query_scalar!(
+        "SELECT actor_name FROM audit_log WHERE target_id = $1",
+        subscriber_id.to_string()
+    )
+    .fetch_all(&test_setup.pg_pool)
+    .await
+    .unwrap();
+    assert_eq!(actors.len(), 2);
+    assert!(actors
+        .iter()
+        .all(|actor| *actor == format!("api_key:{}", api_key_id)));
+}
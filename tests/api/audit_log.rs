@@ -0,0 +1,234 @@
+use crate::test_utils::{self, TestUser};
+use axum::http::StatusCode;
+use serde_json::Value;
+
+impl test_utils::TestSetup {
+    pub async fn get_audit_log(&self, cookie: &str, query: &str) -> Value {
+        let response = self
+            .client
+            .get(&format!("/admin/audit_log?{}", query))
+            .header("Cookie", cookie)
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json().await
+    }
+
+    /// Make every following write to the audit log fail.
+    pub async fn reject_audit_entries(&self) {
+        sqlx::query(
+            r#"
+            CREATE FUNCTION reject_audit_entries() RETURNS trigger AS $$
+            BEGIN RAISE EXCEPTION 'audit log unavailable'; END
+            $$ LANGUAGE plpgsql;
+            "#,
+        )
+        .execute(&self.pg_pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "CREATE TRIGGER reject_audit_entries BEFORE INSERT ON audit_log \
+            FOR EACH ROW EXECUTE FUNCTION reject_audit_entries()",
+        )
+        .execute(&self.pg_pool)
+        .await
+        .unwrap();
+    }
+}
+
+fn actions(page: &Value) -> Vec<&str> {
+    page["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+pub async fn logins_are_recorded() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup
+        .post_login(&test_setup.test_user.username, "wrong password")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+
+    let page = test_setup.get_audit_log(&cookie, "").await;
+
+    assert_eq!(actions(&page), ["login.success", "login.failure"]);
+    let entries = page["entries"].as_array().unwrap();
+    assert_eq!(
+        entries[0]["actor_id"],
+        test_setup.test_user.user_id.to_string()
+    );
+    // A failed login has no actor id, only the name that was tried.
+    assert!(entries[1]["actor_id"].is_null());
+    assert_eq!(entries[1]["actor_name"], test_setup.test_user.username);
+}
+
+#[tokio::test]
+pub async fn admin_actions_are_recorded_with_target_and_request_id() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+    test_setup
+        .client
+        .patch(&format!("/admin/subscribers/{}", id))
+        .header("Cookie", &cookie)
+        .header("X-Request-Id", "test-request-42")
+        .json(&serde_json::json!({ "name": "Ursula" }))
+        .send()
+        .await;
+
+    let page = test_setup
+        .get_audit_log(&cookie, "action=subscriber.update")
+        .await;
+
+    let entries = page["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["actor_name"], test_setup.test_user.username);
+    assert_eq!(entries[0]["target_type"], "subscriber");
+    assert_eq!(entries[0]["target_id"], id.to_string());
+    assert_eq!(entries[0]["request_id"], "test-request-42");
+}
+
+#[tokio::test]
+pub async fn the_audit_log_can_be_filtered_by_target() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (first, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let (second, _) = test_setup
+        .insert_pending_subscriber("octavia@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+    test_setup
+        .post_subscriber_action(&cookie, first, "confirm")
+        .await;
+    test_setup
+        .patch_subscriber(&cookie, second, "Octavia")
+        .await;
+    test_setup.patch_subscriber(&cookie, first, "Ursula").await;
+
+    let page = test_setup
+        .get_audit_log(
+            &cookie,
+            &format!("target_type=subscriber&target_id={}", first),
+        )
+        .await;
+
+    assert_eq!(actions(&page), ["subscriber.update", "subscriber.confirm"]);
+}
+
+#[tokio::test]
+pub async fn the_audit_log_is_paginated() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+    for name in ["Ursula", "Ursula K.", "Ursula K. Le Guin"] {
+        test_setup.patch_subscriber(&cookie, id, name).await;
+    }
+
+    let mut seen = Vec::new();
+    let mut query = "action=subscriber.update&limit=2".to_string();
+    loop {
+        let page = test_setup.get_audit_log(&cookie, &query).await;
+        for entry in page["entries"].as_array().unwrap() {
+            seen.push(entry["audit_id"].as_i64().unwrap());
+        }
+        match page["next_before"].as_i64() {
+            Some(before) => query = format!("action=subscriber.update&limit=2&before={}", before),
+            None => break,
+        }
+    }
+
+    assert_eq!(seen.len(), 3);
+    assert!(seen.windows(2).all(|w| w[0] > w[1]), "not newest first");
+}
+
+#[tokio::test]
+pub async fn only_owners_can_read_the_audit_log() {
+    let test_setup = test_utils::create_test_setup().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&editor).await;
+
+    let response = test_setup
+        .client
+        .get("/admin/audit_log")
+        .header("Cookie", &cookie)
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn audit_log_entries_cannot_be_changed_or_deleted() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup.login_test_user().await;
+
+    let update = sqlx::query!("UPDATE audit_log SET actor_name = 'someone else'")
+        .execute(&test_setup.pg_pool)
+        .await;
+    let delete = sqlx::query!("DELETE FROM audit_log")
+        .execute(&test_setup.pg_pool)
+        .await;
+
+    assert!(update.is_err());
+    assert!(delete.is_err());
+}
+
+#[tokio::test]
+pub async fn an_action_is_undone_when_its_audit_entry_cannot_be_written() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+    test_setup.reject_audit_entries().await;
+
+    let response = test_setup
+        .client
+        .delete(&format!("/admin/subscribers/{}", id))
+        .header("Cookie", &cookie)
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let response = test_setup.get_subscriber(&cookie, id).await;
+    assert_eq!(
+        response.status(),
+        StatusCode::OK,
+        "the subscriber was deleted without an audit entry"
+    );
+}
+
+#[tokio::test]
+pub async fn entries_record_the_client_behind_a_trusted_proxy() {
+    let test_setup = test_utils::create_test_setup_with(|configuration| {
+        configuration.rate_limits.trusted_proxies = vec!["127.0.0.1/32".parse().unwrap()];
+    })
+    .await;
+    test_setup
+        .client
+        .post("/login")
+        .header("X-Forwarded-For", "203.0.113.7")
+        .json(&serde_json::json!({
+            "username": test_setup.test_user.username,
+            "password": "wrong password",
+        }))
+        .send()
+        .await;
+    let cookie = test_setup.login_test_user().await;
+
+    let page = test_setup
+        .get_audit_log(&cookie, "action=login.failure")
+        .await;
+
+    assert_eq!(page["entries"][0]["ip"], "203.0.113.7");
+}
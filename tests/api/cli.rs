@@ -0,0 +1,115 @@
+use crate::test_utils;
+use axum::http::StatusCode;
+use chrono::Utc;
+use secrecy::Secret;
+use zero2prod::authentication::Role;
+use zero2prod::cli::{create_admin, list_subscribers};
+use zero2prod::migrations;
+
+#[tokio::test]
+pub async fn created_admins_can_log_in_with_their_role() {
+    let test_setup = test_utils::create_test_setup().await;
+    let password = "correct horse battery 9";
+
+    let user_id = create_admin(
+        &test_setup.pg_pool,
+        "ada",
+        Secret::new(password.to_string()),
+        Role::Editor,
+        Some("ada@example.com"),
+    )
+    .await
+    .unwrap();
+
+    let response = test_setup.post_login("ada", password).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let user = sqlx::query!("SELECT role, email FROM users WHERE user_id = $1", user_id)
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(user.role, "editor");
+    assert_eq!(user.email.as_deref(), Some("ada@example.com"));
+}
+
+async fn create(
+    test_setup: &test_utils::TestSetup,
+    username: &str,
+    password: &str,
+) -> Result<uuid::Uuid, String> {
+    create_admin(
+        &test_setup.pg_pool,
+        username,
+        Secret::new(password.to_string()),
+        Role::Owner,
+        None,
+    )
+    .await
+}
+
+#[tokio::test]
+pub async fn create_admin_rejects_weak_passwords_and_taken_usernames() {
+    let test_setup = test_utils::create_test_setup().await;
+    assert!(create(&test_setup, "ada", "short").await.is_err());
+    assert!(create(&test_setup, "ada", "onlyletterslongenough")
+        .await
+        .is_err());
+    assert!(create(
+        &test_setup,
+        &test_setup.test_user.username,
+        "correct horse battery 9"
+    )
+    .await
+    .is_err());
+    assert!(create(&test_setup, "ada", "correct horse battery 9")
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+pub async fn list_subscribers_prints_tab_separated_rows() {
+    let test_setup = test_utils::create_test_setup().await;
+    let ada = test_setup
+        .insert_subscriber("ada@example.com", "Ada", "confirmed", Utc::now())
+        .await;
+    test_setup
+        .insert_subscriber(
+            "grace@example.com",
+            "Grace",
+            "pending_confirmation",
+            Utc::now(),
+        )
+        .await;
+
+    let mut out = Vec::new();
+    list_subscribers(&test_setup.pg_pool, Some("confirmed"), None, &mut out)
+        .await
+        .unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let columns: Vec<&str> = lines[0].split('\t').collect();
+    assert_eq!(
+        columns[..4],
+        [
+            ada.to_string().as_str(),
+            "ada@example.com",
+            "Ada",
+            "confirmed"
+        ]
+    );
+    assert!(
+        list_subscribers(&test_setup.pg_pool, Some("deleted"), None, &mut Vec::new())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+pub async fn a_migrated_database_has_no_pending_migrations() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let pending = migrations::pending(&test_setup.pg_pool).await.unwrap();
+
+    assert!(pending.is_empty());
+}
@@ -11,7 +11,7 @@ pub trait QueryParams {}
 
 #[derive(Debug, serde::Serialize)]
 pub struct CorrectQueryParams {
-    token: String,
+    pub token: String,
 }
 impl QueryParams for CorrectQueryParams {}
 
@@ -49,8 +49,8 @@ pub fn get_confirmation_links(email_request: &wiremock::Request) -> String {
         // confirmation_link.set_port(Some(self.port)).unwrap();
         // confirmation_link
     };
-    let html = get_link(&body["HtmlBody"].as_str().unwrap());
-    let plain_text = get_link(&body["TextBody"].as_str().unwrap());
+    let html = get_link(body["HtmlBody"].as_str().unwrap());
+    let plain_text = get_link(body["TextBody"].as_str().unwrap());
     // The two links should be identical
     assert_eq!(html, plain_text);
     plain_text
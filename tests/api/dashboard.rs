@@ -0,0 +1,270 @@
+use crate::test_utils::{self, TestUser};
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use chrono::Utc;
+use std::collections::BTreeMap;
+
+/// The dashboard as a browser sees it: cookies are kept between requests, and the
+/// CSRF token of the last page is sent with the forms.
+pub struct Browser<'a> {
+    test_setup: &'a test_utils::TestSetup,
+    cookies: BTreeMap<String, String>,
+    csrf_token: Option<String>,
+}
+
+pub struct Page {
+    pub status: StatusCode,
+    pub location: Option<String>,
+    pub body: String,
+}
+
+impl<'a> Browser<'a> {
+    pub fn new(test_setup: &'a test_utils::TestSetup) -> Self {
+        Self {
+            test_setup,
+            cookies: BTreeMap::new(),
+            csrf_token: None,
+        }
+    }
+
+    pub async fn get(&mut self, path: &str) -> Page {
+        let response = self
+            .test_setup
+            .client
+            .get(path)
+            .header("Cookie", self.cookie_header())
+            .send()
+            .await;
+        let page = self.read(response).await;
+        if let Some(token) = csrf_token(&page.body) {
+            self.csrf_token = Some(token);
+        }
+        page
+    }
+
+    /// Submit a form with the CSRF token of the last page.
+    pub async fn post(&mut self, path: &str, fields: &[(&str, &str)]) -> Page {
+        let token = self.csrf_token.clone().unwrap_or_default();
+        let mut form = vec![("csrf_token", token.as_str())];
+        form.extend_from_slice(fields);
+        self.post_raw(path, &form).await
+    }
+
+    pub async fn post_raw(&mut self, path: &str, form: &[(&str, &str)]) -> Page {
+        let response = self
+            .test_setup
+            .client
+            .post(path)
+            .header("Cookie", self.cookie_header())
+            .form(form)
+            .send()
+            .await;
+        self.read(response).await
+    }
+
+    pub async fn login(&mut self, user: &TestUser) -> Page {
+        self.get("/dashboard/login").await;
+        self.post(
+            "/dashboard/login",
+            &[("username", &user.username), ("password", &user.password)],
+        )
+        .await
+    }
+
+    fn cookie_header(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    async fn read(&mut self, response: TestResponse) -> Page {
+        for set_cookie in response.headers().get_all("Set-Cookie") {
+            let set_cookie = set_cookie.to_str().unwrap();
+            let (name, value) = set_cookie
+                .split(';')
+                .next()
+                .unwrap()
+                .split_once('=')
+                .unwrap();
+            if value.is_empty() || set_cookie.contains("Max-Age=0") {
+                self.cookies.remove(name);
+            } else {
+                self.cookies.insert(name.to_string(), value.to_string());
+            }
+        }
+        Page {
+            status: response.status(),
+            location: response
+                .headers()
+                .get("Location")
+                .map(|l| l.to_str().unwrap().to_string()),
+            body: response.text().await,
+        }
+    }
+}
+
+fn csrf_token(body: &str) -> Option<String> {
+    let start = body.find(r#"name="csrf_token" value=""#)? + r#"name="csrf_token" value=""#.len();
+    let end = body[start..].find('"')?;
+    Some(body[start..start + end].to_string())
+}
+
+#[tokio::test]
+pub async fn pages_redirect_to_the_login_without_a_session() {
+    let test_setup = test_utils::create_test_setup().await;
+    let mut browser = Browser::new(&test_setup);
+
+    for path in ["/dashboard", "/dashboard/subscribers"] {
+        let page = browser.get(path).await;
+        assert_eq!(page.status, StatusCode::SEE_OTHER, "{}", path);
+        assert_eq!(page.location.as_deref(), Some("/dashboard/login"));
+    }
+}
+
+#[tokio::test]
+pub async fn the_dashboard_shows_subscriber_counts_after_login() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup
+        .insert_subscriber("ada@example.com", "Ada", "confirmed", Utc::now())
+        .await;
+    test_setup
+        .insert_subscriber("grace@example.com", "Grace", "confirmed", Utc::now())
+        .await;
+    let mut browser = Browser::new(&test_setup);
+
+    let page = browser.login(&test_setup.test_user).await;
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert_eq!(page.location.as_deref(), Some("/dashboard"));
+
+    let page = browser.get("/dashboard").await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.body.contains(
+        r#"<a href="/dashboard/subscribers?status=confirmed">confirmed</a></td><td>2</td>"#
+    ));
+    assert!(page.body.contains(&test_setup.test_user.username));
+}
+
+#[tokio::test]
+pub async fn forms_without_the_csrf_token_are_rejected() {
+    let test_setup = test_utils::create_test_setup().await;
+    let user = &test_setup.test_user;
+    let mut browser = Browser::new(&test_setup);
+    browser.get("/dashboard/login").await;
+
+    let credentials = [
+        ("username", user.username.as_str()),
+        ("password", user.password.as_str()),
+    ];
+    let page = browser
+        .post_raw(
+            "/dashboard/login",
+            &[credentials[0], credentials[1], ("csrf_token", "forged")],
+        )
+        .await;
+    assert_eq!(page.status, StatusCode::FORBIDDEN);
+
+    // A token from another browser does not help either.
+    let mut other = Browser::new(&test_setup);
+    other.get("/dashboard/login").await;
+    let token = other.csrf_token.clone().unwrap();
+    let page = browser
+        .post_raw(
+            "/dashboard/login",
+            &[credentials[0], credentials[1], ("csrf_token", &token)],
+        )
+        .await;
+    assert_eq!(page.status, StatusCode::FORBIDDEN);
+
+    let sessions = sqlx::query!("SELECT count(*) AS \"count!\" FROM sessions")
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(sessions.count, 0);
+}
+
+#[tokio::test]
+pub async fn malformed_forms_are_rejected_without_a_flash_message() {
+    let test_setup = test_utils::create_test_setup().await;
+    let mut browser = Browser::new(&test_setup);
+    browser.get("/dashboard/login").await;
+
+    // The form is read whole, token included, before the handler can leave a message.
+    let page = browser
+        .post_raw("/dashboard/login", &[("csrf_token", "forged")])
+        .await;
+
+    assert_eq!(page.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(page.location, None);
+    assert!(!browser.cookies.contains_key("flash"));
+}
+
+#[tokio::test]
+pub async fn a_wrong_password_is_shown_once_as_flash_message() {
+    let test_setup = test_utils::create_test_setup().await;
+    let mut browser = Browser::new(&test_setup);
+    browser.get("/dashboard/login").await;
+
+    let page = browser
+        .post(
+            "/dashboard/login",
+            &[
+                ("username", &test_setup.test_user.username),
+                ("password", "not-the-password"),
+            ],
+        )
+        .await;
+    assert_eq!(page.location.as_deref(), Some("/dashboard/login"));
+
+    let page = browser.get("/dashboard/login").await;
+    assert!(page.body.contains("Wrong username or password."));
+    let page = browser.get("/dashboard/login").await;
+    assert!(!page.body.contains("Wrong username or password."));
+}
+
+#[tokio::test]
+pub async fn subscribers_are_listed_escaped_and_filtered() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup
+        .insert_subscriber("ada@example.com", "<b>Ada</b>", "confirmed", Utc::now())
+        .await;
+    test_setup
+        .insert_subscriber(
+            "grace@example.com",
+            "Grace",
+            "pending_confirmation",
+            Utc::now(),
+        )
+        .await;
+    let mut browser = Browser::new(&test_setup);
+    browser.login(&test_setup.test_user).await;
+
+    let page = browser.get("/dashboard/subscribers?status=&q=").await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.body.contains("&lt;b&gt;Ada&lt;/b&gt;"));
+    assert!(!page.body.contains("<b>Ada</b>"));
+    assert!(page.body.contains("grace@example.com"));
+
+    let page = browser
+        .get("/dashboard/subscribers?status=pending_confirmation&q=")
+        .await;
+    assert!(!page.body.contains("ada@example.com"));
+    assert!(page.body.contains("grace@example.com"));
+}
+
+#[tokio::test]
+pub async fn logging_out_ends_the_session() {
+    let test_setup = test_utils::create_test_setup().await;
+    let mut browser = Browser::new(&test_setup);
+    browser.login(&test_setup.test_user).await;
+    browser.get("/dashboard").await;
+
+    let page = browser.post("/dashboard/logout", &[]).await;
+    assert_eq!(page.location.as_deref(), Some("/dashboard/login"));
+
+    let page = browser.get("/dashboard").await;
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    let page = browser.get("/dashboard/login").await;
+    assert!(page.body.contains("You have been logged out."));
+}
@@ -0,0 +1,261 @@
+use crate::test_utils;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::Router;
+use axum_test_helper::TestClient;
+use std::net::TcpListener;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use zero2prod::configuration::HttpSettings;
+use zero2prod::hardening::HttpConfig;
+use zero2prod::shutdown::{self, CancellationToken};
+
+fn config(settings: HttpSettings) -> HttpConfig {
+    HttpConfig::try_from(&settings).expect("Invalid settings.")
+}
+
+// A small app behind the hardening layers, with a signup-like endpoint.
+fn app(settings: HttpSettings) -> Router {
+    let config = config(settings);
+    let routes = Router::new()
+        .route("/", get(|| async { "x".repeat(4096) }))
+        .route("/echo", post(|body: String| async move { body }))
+        .route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                "done"
+            }),
+        )
+        .route(
+            "/subscribe",
+            post(|| async { "subscribed" }).layer(config.cors()),
+        );
+    config.apply(routes)
+}
+
+#[tokio::test]
+pub async fn responses_carry_the_security_headers() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup.client.get("/health_check").send().await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+    assert_eq!(headers["Strict-Transport-Security"], "max-age=31536000");
+    assert!(headers["Content-Security-Policy"]
+        .to_str()
+        .unwrap()
+        .contains("frame-ancestors 'none'"));
+    assert_eq!(headers["Referrer-Policy"], "no-referrer");
+}
+
+#[tokio::test]
+pub async fn empty_settings_leave_the_optional_headers_out() {
+    let client = TestClient::new(app(HttpSettings {
+        hsts_max_age_seconds: 0,
+        content_security_policy: "".into(),
+        referrer_policy: "".into(),
+        ..Default::default()
+    }));
+
+    let response = client.get("/").send().await;
+
+    let headers = response.headers();
+    assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+    assert!(headers.get("Strict-Transport-Security").is_none());
+    assert!(headers.get("Content-Security-Policy").is_none());
+    assert!(headers.get("Referrer-Policy").is_none());
+}
+
+#[test]
+pub fn invalid_header_settings_are_rejected() {
+    let result = HttpConfig::try_from(&HttpSettings {
+        cors_allowed_origins: vec!["https://example.com\n".into()],
+        ..Default::default()
+    });
+
+    assert!(result.unwrap_err().contains("cors_allowed_origins"));
+}
+
+#[tokio::test]
+pub async fn allowed_origins_may_call_the_signup_endpoints() {
+    let client = TestClient::new(app(HttpSettings {
+        cors_allowed_origins: vec!["https://blog.example.com".into()],
+        ..Default::default()
+    }));
+
+    let response = client
+        .post("/subscribe")
+        .header("Origin", "https://blog.example.com")
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["Access-Control-Allow-Origin"],
+        "https://blog.example.com"
+    );
+
+    let response = client
+        .post("/subscribe")
+        .header("Origin", "https://evil.example.com")
+        .send()
+        .await;
+    assert!(response
+        .headers()
+        .get("Access-Control-Allow-Origin")
+        .is_none());
+}
+
+#[tokio::test]
+pub async fn preflight_requests_are_answered_for_allowed_origins_only() {
+    let app = app(HttpSettings {
+        cors_allowed_origins: vec!["https://blog.example.com".into()],
+        ..Default::default()
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = CancellationToken::new();
+    tokio::spawn(shutdown::serve(
+        listener,
+        app,
+        None,
+        shutdown.clone(),
+        Duration::from_secs(1),
+    ));
+    let preflight = |origin: &'static str| async move {
+        reqwest::Client::new()
+            .request(
+                reqwest::Method::OPTIONS,
+                format!("http://{}/subscribe", addr),
+            )
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "POST")
+            .header("Access-Control-Request-Headers", "content-type")
+            .send()
+            .await
+            .unwrap()
+    };
+
+    let response = preflight("https://blog.example.com").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(
+        headers["Access-Control-Allow-Origin"],
+        "https://blog.example.com"
+    );
+    assert_eq!(headers["Access-Control-Allow-Methods"], "POST");
+    assert_eq!(headers["Access-Control-Allow-Headers"], "content-type");
+    assert!(headers.get("Access-Control-Allow-Credentials").is_none());
+
+    let response = preflight("https://evil.example.com").await;
+    assert!(response
+        .headers()
+        .get("Access-Control-Allow-Origin")
+        .is_none());
+
+    shutdown.cancel();
+}
+
+#[tokio::test]
+pub async fn bodies_over_the_limit_are_rejected() {
+    let client = TestClient::new(app(HttpSettings {
+        body_limit_bytes: 16,
+        ..Default::default()
+    }));
+
+    let response = client.post("/echo").body("a".repeat(16)).send().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await, "a".repeat(16));
+
+    let response = client.post("/echo").body("a".repeat(17)).send().await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+}
+
+#[tokio::test]
+pub async fn slow_bodies_are_answered_with_408() {
+    let app = app(HttpSettings {
+        body_timeout_seconds: 1,
+        ..Default::default()
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = CancellationToken::new();
+    tokio::spawn(shutdown::serve(
+        listener,
+        app,
+        None,
+        shutdown.clone(),
+        Duration::from_secs(1),
+    ));
+
+    // Promise ten bytes, send three and wait.
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc")
+        .await
+        .unwrap();
+    let mut response = vec![0; 1024];
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut response))
+        .await
+        .expect("No response to the slow body.")
+        .unwrap();
+
+    let response = String::from_utf8_lossy(&response[..read]);
+    assert!(
+        response.starts_with("HTTP/1.1 408"),
+        "Unexpected response: {}",
+        response
+    );
+    shutdown.cancel();
+}
+
+#[tokio::test]
+pub async fn slow_handlers_are_answered_with_503() {
+    let client = TestClient::new(app(HttpSettings {
+        request_timeout_seconds: 1,
+        ..Default::default()
+    }));
+
+    let response = client.get("/slow").send().await;
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    // The security headers are set on these responses too.
+    assert_eq!(response.headers()["X-Content-Type-Options"], "nosniff");
+}
+
+#[tokio::test]
+pub async fn responses_are_compressed_if_the_client_accepts_it() {
+    let client = TestClient::new(app(HttpSettings::default()));
+
+    let response = client
+        .get("/")
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await;
+    assert_eq!(response.headers()["Content-Encoding"], "gzip");
+    assert!(response.bytes().await.len() < 4096);
+
+    let response = client.get("/").send().await;
+    assert!(response.headers().get("Content-Encoding").is_none());
+
+    let client = TestClient::new(app(HttpSettings {
+        compression: false,
+        ..Default::default()
+    }));
+    let response = client
+        .get("/")
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await;
+    assert!(response.headers().get("Content-Encoding").is_none());
+}
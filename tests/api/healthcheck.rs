@@ -1,5 +1,30 @@
 use crate::test_utils;
 use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use chrono::Utc;
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+use wiremock::matchers::method;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::worker::record_heartbeat;
+
+impl test_utils::TestSetup {
+    pub async fn get_ready(&self) -> TestResponse {
+        self.client.get("/health/ready").send().await
+    }
+
+    // The email provider answers and a worker is alive.
+    pub async fn make_ready(&self) {
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.email_server)
+            .await;
+        record_heartbeat(&self.pg_pool, Uuid::new_v4(), Utc::now())
+            .await
+            .unwrap();
+    }
+}
 
 #[tokio::test]
 pub async fn health_check_works() {
@@ -8,3 +33,137 @@ pub async fn health_check_works() {
     let res = test_setup.client.get("/health_check").send().await;
     assert_eq!(res.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+pub async fn the_liveness_probe_answers() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup.client.get("/health/live").send().await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["status"], "live");
+}
+
+#[tokio::test]
+pub async fn the_readiness_probe_reports_every_component() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup.make_ready().await;
+
+    let response = test_setup.get_ready().await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["status"], "ready");
+    for component in ["database", "migrations", "email_provider", "worker"] {
+        let check = &body["checks"][component];
+        assert_eq!(check["status"], "up", "{} is down: {}", component, check);
+        assert!(check["latency_ms"].as_f64().unwrap() >= 0.0);
+    }
+}
+
+#[tokio::test]
+pub async fn a_stale_worker_heartbeat_makes_the_app_degraded_but_ready() {
+    let test_setup = test_utils::create_test_setup().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_setup.email_server)
+        .await;
+    // A worker that stopped long ago.
+    record_heartbeat(&test_setup.pg_pool, Uuid::new_v4(), Utc::now())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE worker_heartbeats SET beat_at = now() - interval '10 minutes'")
+        .execute(&test_setup.pg_pool)
+        .await
+        .unwrap();
+
+    let response = test_setup.get_ready().await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["status"], "degraded");
+    assert_eq!(body["checks"]["worker"]["status"], "down");
+}
+
+#[tokio::test]
+pub async fn the_readiness_probe_does_not_say_why_a_component_is_down() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup.make_ready().await;
+    sqlx::query(
+        "DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)",
+    )
+    .execute(&test_setup.pg_pool)
+    .await
+    .unwrap();
+
+    let response = test_setup.get_ready().await;
+
+    let body: Value = response.json().await;
+    let check = body["checks"]["migrations"].as_object().unwrap();
+    assert_eq!(check["status"], "down");
+    let mut fields: Vec<_> = check.keys().map(String::as_str).collect();
+    fields.sort();
+    assert_eq!(fields, ["latency_ms", "status"]);
+}
+
+#[tokio::test]
+pub async fn the_email_provider_is_checked_once_per_ttl() {
+    let test_setup = test_utils::create_test_setup().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_setup.email_server)
+        .await;
+    record_heartbeat(&test_setup.pg_pool, Uuid::new_v4(), Utc::now())
+        .await
+        .unwrap();
+
+    for _ in 0..3 {
+        let response = test_setup.get_ready().await;
+        let body: Value = response.json().await;
+        assert_eq!(body["checks"]["email_provider"]["status"], "up");
+    }
+    // The mock checks on drop that the provider was asked once.
+}
+
+#[tokio::test]
+pub async fn a_hanging_email_provider_fails_its_check_after_the_timeout() {
+    let test_setup = test_utils::create_test_setup().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+        .mount(&test_setup.email_server)
+        .await;
+    record_heartbeat(&test_setup.pg_pool, Uuid::new_v4(), Utc::now())
+        .await
+        .unwrap();
+
+    let response = test_setup.get_ready().await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["status"], "degraded");
+    let check = &body["checks"]["email_provider"];
+    assert_eq!(check["status"], "down");
+    assert!(check["latency_ms"].as_f64().unwrap() < 10_000.0);
+}
+
+#[tokio::test]
+pub async fn pending_migrations_make_the_app_unavailable() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup.make_ready().await;
+    sqlx::query(
+        "DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)",
+    )
+    .execute(&test_setup.pg_pool)
+    .await
+    .unwrap();
+
+    let response = test_setup.get_ready().await;
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = response.json().await;
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["checks"]["migrations"]["status"], "down");
+    assert_eq!(body["checks"]["database"]["status"], "up");
+}
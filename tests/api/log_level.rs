@@ -0,0 +1,79 @@
+use crate::test_utils::{self, TestUser};
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use serde_json::{json, Value};
+
+impl test_utils::TestSetup {
+    pub async fn get_log_level(&self, cookie: &str) -> TestResponse {
+        self.client
+            .get("/admin/log_level")
+            .header("Cookie", cookie)
+            .send()
+            .await
+    }
+
+    pub async fn put_log_level(&self, cookie: &str, directive: &str) -> TestResponse {
+        self.client
+            .put("/admin/log_level")
+            .header("Cookie", cookie)
+            .json(&json!({ "directive": directive }))
+            .send()
+            .await
+    }
+}
+
+// The filter is global to the test binary, so this is the only test that changes it,
+// and every directive it sets keeps the `info` spans the other tests rely on.
+#[tokio::test]
+pub async fn owners_can_change_the_log_filter_at_runtime() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup.put_log_level(&cookie, "info,sqlx=warn").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert!(body["directive"].as_str().unwrap().contains("sqlx=warn"));
+
+    let response = test_setup.get_log_level(&cookie).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert!(body["directive"].as_str().unwrap().contains("sqlx=warn"));
+
+    let response = test_setup.put_log_level(&cookie, "info").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = test_setup.get_log_level(&cookie).await.json().await;
+    assert_eq!(body["directive"], "info");
+
+    let audit_log = test_setup
+        .get_audit_log(&cookie, "action=log_level.set")
+        .await;
+    assert_eq!(audit_log["entries"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+pub async fn an_invalid_log_filter_is_rejected() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup.put_log_level(&cookie, "info,[").await;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: Value = response.json().await;
+    assert!(problem["detail"].as_str().unwrap().contains("info,["));
+}
+
+#[tokio::test]
+pub async fn only_owners_can_see_or_change_the_log_filter() {
+    let test_setup = test_utils::create_test_setup().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&editor).await;
+
+    let response = test_setup.get_log_level(&cookie).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = test_setup.put_log_level(&cookie, "debug").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = test_setup.client.get("/admin/log_level").send().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,235 @@
+use crate::test_utils;
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use zero2prod::authentication::session::delete_expired_sessions;
+use zero2prod::authentication::SessionConfig;
+use zero2prod::configuration::get_configuration;
+
+impl test_utils::TestSetup {
+    pub async fn post_login(&self, username: &str, password: &str) -> TestResponse {
+        self.client
+            .post("/login")
+            .json(&serde_json::json!({
+                "username": username,
+                "password": password,
+            }))
+            .send()
+            .await
+    }
+
+    /// Log in as the test user and return the session cookie.
+    pub async fn login_test_user(&self) -> String {
+        self.login_as(&self.test_user).await
+    }
+
+    /// Log in as `user` and return the session cookie.
+    pub async fn login_as(&self, user: &test_utils::TestUser) -> String {
+        let response = self.post_login(&user.username, &user.password).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        session_cookie(&response)
+    }
+
+    pub async fn post_logout(&self, cookie: &str) -> TestResponse {
+        self.client
+            .post("/logout")
+            .header("Cookie", cookie)
+            .send()
+            .await
+    }
+
+    pub async fn get_admin_me(&self, cookie: &str) -> TestResponse {
+        self.client
+            .get("/admin/me")
+            .header("Cookie", cookie)
+            .send()
+            .await
+    }
+}
+
+// The `name=value` part of the session cookie set by a response.
+pub fn session_cookie(response: &TestResponse) -> String {
+    let set_cookie = response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .find(|value| value.starts_with("session_id="))
+        .expect("No session cookie was set.");
+    set_cookie.split(';').next().unwrap().to_string()
+}
+
+#[tokio::test]
+pub async fn login_sets_an_http_only_session_cookie() {
+    let test_setup = test_utils::create_test_setup().await;
+    let user = &test_setup.test_user;
+
+    let response = test_setup.post_login(&user.username, &user.password).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let set_cookie = response.headers()["Set-Cookie"].to_str().unwrap();
+    assert!(set_cookie.contains("HttpOnly"));
+    // The cookie is encrypted, it does not contain the raw session id.
+    let cookie = session_cookie(&response);
+    let session_id = sqlx::query!("SELECT session_id FROM sessions")
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap()
+        .session_id;
+    assert!(!cookie.contains(&session_id));
+}
+
+#[tokio::test]
+pub async fn login_fails_with_wrong_password() {
+    let test_setup = test_utils::create_test_setup().await;
+    let user = &test_setup.test_user;
+
+    let response = test_setup
+        .post_login(&user.username, "wrong-password")
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().get("Set-Cookie").is_none());
+}
+
+#[tokio::test]
+pub async fn login_fails_for_unknown_user() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup.post_login("nobody", "wrong-password").await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn admin_routes_reject_requests_without_a_session() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup.client.get("/admin/me").send().await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn admin_routes_reject_a_forged_session_cookie() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup.login_test_user().await;
+    let session_id = sqlx::query!("SELECT session_id FROM sessions")
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap()
+        .session_id;
+
+    // The raw id is useless without the encryption key.
+    let response = test_setup
+        .get_admin_me(&format!("session_id={}", session_id))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn admin_routes_accept_a_valid_session() {
+    let test_setup = test_utils::create_test_setup().await;
+    let user = &test_setup.test_user;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup.get_admin_me(&cookie).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = response.json().await;
+    assert_eq!(body["username"], user.username.as_str());
+    assert_eq!(body["user_id"], user.user_id.to_string());
+    assert_eq!(body["role"], "owner");
+}
+
+#[tokio::test]
+pub async fn logout_ends_the_session() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup.post_logout(&cookie).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = test_setup.get_admin_me(&cookie).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn login_rotates_the_session_id() {
+    let test_setup = test_utils::create_test_setup().await;
+    let user = &test_setup.test_user;
+    let old_cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .client
+        .post("/login")
+        .header("Cookie", &old_cookie)
+        .json(&serde_json::json!({
+            "username": user.username,
+            "password": user.password,
+        }))
+        .send()
+        .await;
+    let new_cookie = session_cookie(&response);
+
+    assert_ne!(old_cookie, new_cookie);
+    let response = test_setup.get_admin_me(&old_cookie).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = test_setup.get_admin_me(&new_cookie).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn idle_sessions_expire() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    sqlx::query!("UPDATE sessions SET last_seen_at = now() - interval '1 day'")
+        .execute(&test_setup.pg_pool)
+        .await
+        .unwrap();
+
+    let response = test_setup.get_admin_me(&cookie).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn sessions_expire_after_the_absolute_timeout_even_if_used() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    sqlx::query!("UPDATE sessions SET expires_at = now() - interval '1 second'")
+        .execute(&test_setup.pg_pool)
+        .await
+        .unwrap();
+
+    let response = test_setup.get_admin_me(&cookie).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn expired_sessions_are_deleted() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup.login_test_user().await;
+    test_setup.login_test_user().await;
+    sqlx::query!(
+        "UPDATE sessions SET expires_at = now() - interval '1 second'
+        WHERE session_id = (SELECT session_id FROM sessions LIMIT 1)"
+    )
+    .execute(&test_setup.pg_pool)
+    .await
+    .unwrap();
+
+    let configuration = get_configuration().unwrap();
+    let config = SessionConfig::try_from(&configuration.session).unwrap();
+    let deleted = delete_expired_sessions(&test_setup.pg_pool, &config)
+        .await
+        .unwrap();
+
+    assert_eq!(deleted, 1);
+    let remaining = sqlx::query!("SELECT count(*) AS count FROM sessions")
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.count, Some(1));
+}
@@ -1,6 +1,23 @@
 // This file exists so that api is seen as its own crate, containing all tests.
 // default behaviour is that every file under tests is a crate.
+mod api_keys;
+mod audit_log;
+mod cli;
 mod confirm;
+mod dashboard;
+mod hardening;
 mod healthcheck;
+mod log_level;
+mod login;
+mod metrics;
+mod mfa;
+mod password;
+mod rate_limit;
+mod reload;
+mod request_id;
+mod roles;
 mod subscribe;
+mod subscribers;
+mod telemetry;
 mod test_utils;
+mod tls;
@@ -0,0 +1,128 @@
+use crate::subscribe::SubscribeRequest;
+use crate::test_utils;
+use axum::http::StatusCode;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+impl test_utils::TestSetup {
+    pub async fn get_metrics(&self) -> String {
+        let response = self
+            .client
+            .get("/metrics")
+            .header("Authorization", format!("Bearer {}", test_utils::METRICS_TOKEN))
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        response.text().await
+    }
+}
+
+// The recorder is shared by all tests of the process, so the values are not known,
+// only which series exist.
+fn has_series(metrics: &str, series: &str) -> bool {
+    metrics.lines().any(|line| line.starts_with(series))
+}
+
+#[tokio::test]
+pub async fn requests_are_counted_by_route_pattern_and_status() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup.client.get("/health_check").send().await;
+    test_setup
+        .client
+        .get(&format!("/admin/subscribers/{}", uuid::Uuid::new_v4()))
+        .send()
+        .await;
+    test_setup.client.get("/does-not-exist").send().await;
+
+    let metrics = test_setup.get_metrics().await;
+
+    assert!(has_series(
+        &metrics,
+        r#"http_requests_total{method="GET",route="/health_check",status="200"}"#
+    ));
+    assert!(has_series(
+        &metrics,
+        r#"http_requests_total{method="GET",route="/admin/subscribers/:subscriber_id",status="401"}"#
+    ));
+    assert!(has_series(
+        &metrics,
+        r#"http_requests_total{method="GET",route="unmatched",status="404"}"#
+    ));
+    assert!(has_series(
+        &metrics,
+        r#"http_request_duration_seconds_bucket{method="GET",route="/health_check",status="200",le="0.005"}"#
+    ));
+}
+
+#[tokio::test]
+pub async fn subscriptions_and_emails_are_counted() {
+    let test_setup = test_utils::create_test_setup().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_setup.email_server)
+        .await;
+
+    test_setup
+        .post_subscriptions(&SubscribeRequest {
+            email: "ada@example.com".into(),
+            name: "Ada".into(),
+        })
+        .await;
+
+    let metrics = test_setup.get_metrics().await;
+    assert!(has_series(&metrics, "subscriptions_created_total"));
+    assert!(has_series(
+        &metrics,
+        r#"email_send_attempts_total{provider="postmark"}"#
+    ));
+    assert!(has_series(
+        &metrics,
+        r#"email_send_duration_seconds_count{provider="postmark"}"#
+    ));
+}
+
+#[tokio::test]
+pub async fn the_connection_pool_is_reported() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let metrics = test_setup.get_metrics().await;
+
+    for gauge in [
+        "db_pool_connections ",
+        "db_pool_idle_connections ",
+        "db_pool_max_connections ",
+        "db_pool_acquire_wait_seconds ",
+    ] {
+        assert!(has_series(&metrics, gauge), "{} is missing", gauge);
+    }
+}
+
+#[tokio::test]
+pub async fn metrics_need_the_bearer_token() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let without = test_setup.client.get("/metrics").send().await;
+    let wrong = test_setup
+        .client
+        .get("/metrics")
+        .header("Authorization", "Bearer not-the-token")
+        .send()
+        .await;
+
+    for response in [without, wrong] {
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    }
+}
+
+#[tokio::test]
+pub async fn metrics_are_not_served_without_a_configured_token() {
+    let test_setup = test_utils::create_test_setup_with(|settings| {
+        settings.metrics.bearer_token = None;
+    })
+    .await;
+
+    let response = test_setup.client.get("/metrics").send().await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
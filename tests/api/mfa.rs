@@ -0,0 +1,242 @@
+use crate::login::session_cookie;
+use crate::test_utils;
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use serde_json::{json, Value};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+impl test_utils::TestSetup {
+    pub async fn post_login_mfa(&self, cookie: &str, code: &str) -> TestResponse {
+        self.client
+            .post("/login/mfa")
+            .header("Cookie", cookie)
+            .json(&json!({ "code": code }))
+            .send()
+            .await
+    }
+
+    pub async fn post_totp_enrolment(&self, cookie: &str) -> TestResponse {
+        self.client
+            .post("/admin/mfa/totp")
+            .header("Cookie", cookie)
+            .send()
+            .await
+    }
+
+    pub async fn post_totp_confirmation(&self, cookie: &str, code: &str) -> TestResponse {
+        self.client
+            .post("/admin/mfa/totp/confirm")
+            .header("Cookie", cookie)
+            .json(&json!({ "code": code }))
+            .send()
+            .await
+    }
+
+    /// Enrol the test user in TOTP. Returns their authenticator and recovery codes.
+    pub async fn enable_totp(&self) -> (TOTP, Vec<String>) {
+        let cookie = self.login_test_user().await;
+        let enrolment: Value = self.post_totp_enrolment(&cookie).await.json().await;
+        let totp = authenticator(enrolment["secret"].as_str().unwrap());
+
+        let response = self
+            .post_totp_confirmation(&cookie, &totp.generate_current().unwrap())
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await;
+        let recovery_codes = body["recovery_codes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c.as_str().unwrap().to_string())
+            .collect();
+        (totp, recovery_codes)
+    }
+
+    /// Enter the password of the test user and return the MFA pending session cookie.
+    pub async fn login_first_step(&self) -> String {
+        let response = self
+            .post_login(&self.test_user.username, &self.test_user.password)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = session_cookie(&response);
+        let body: Value = response.json().await;
+        assert_eq!(body["mfa_required"], true);
+        cookie
+    }
+}
+
+fn authenticator(secret: &str) -> TOTP {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    TOTP::new_unchecked(Algorithm::SHA1, 6, 0, 30, secret, None, String::new())
+}
+
+// The enrolment used the current code, which cannot be used again. Authenticator apps
+// may run a little ahead, so the next one is accepted.
+fn next_code(totp: &TOTP) -> String {
+    totp.generate(totp.next_step_current().unwrap())
+}
+
+#[tokio::test]
+pub async fn enrolment_returns_an_otpauth_uri() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup.post_totp_enrolment(&cookie).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    let secret = body["secret"].as_str().unwrap();
+    let uri = body["otpauth_uri"].as_str().unwrap();
+    assert!(uri.starts_with("otpauth://totp/zero2prod:"));
+    assert!(uri.contains(&format!("secret={}", secret)));
+}
+
+#[tokio::test]
+pub async fn totp_secrets_are_encrypted_at_rest() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+    let body: Value = test_setup.post_totp_enrolment(&cookie).await.json().await;
+    let secret = Secret::Encoded(body["secret"].as_str().unwrap().to_string())
+        .to_bytes()
+        .unwrap();
+
+    let stored = sqlx::query!(
+        r#"SELECT totp_secret AS "totp_secret!" FROM users WHERE user_id = $1"#,
+        test_setup.test_user.user_id,
+    )
+    .fetch_one(&test_setup.pg_pool)
+    .await
+    .unwrap()
+    .totp_secret;
+
+    assert!(!stored.windows(secret.len()).any(|w| w == secret));
+}
+
+#[tokio::test]
+pub async fn login_does_not_require_a_code_until_enrolment_is_confirmed() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+    test_setup.post_totp_enrolment(&cookie).await;
+
+    let response = test_setup
+        .post_login(
+            &test_setup.test_user.username,
+            &test_setup.test_user.password,
+        )
+        .await;
+
+    let body: Value = response.json().await;
+    assert_eq!(body["mfa_required"], false);
+}
+
+#[tokio::test]
+pub async fn enrolment_is_not_confirmed_with_a_wrong_code() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+    let body: Value = test_setup.post_totp_enrolment(&cookie).await.json().await;
+    let totp = authenticator(body["secret"].as_str().unwrap());
+    let current = totp.generate_current().unwrap();
+    let wrong = if current == "000000" {
+        "111111"
+    } else {
+        "000000"
+    };
+
+    let response = test_setup.post_totp_confirmation(&cookie, wrong).await;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+pub async fn login_requires_a_code_once_enrolled() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (totp, _) = test_setup.enable_totp().await;
+
+    let pending_cookie = test_setup.login_first_step().await;
+    // The password alone does not give access to the admin routes.
+    let response = test_setup.get_admin_me(&pending_cookie).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = test_setup
+        .post_login_mfa(&pending_cookie, &next_code(&totp))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = session_cookie(&response);
+    assert_ne!(cookie, pending_cookie);
+    let response = test_setup.get_admin_me(&cookie).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn enrolment_cannot_be_restarted_once_enabled() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (totp, _) = test_setup.enable_totp().await;
+    let pending_cookie = test_setup.login_first_step().await;
+    let response = test_setup
+        .post_login_mfa(&pending_cookie, &next_code(&totp))
+        .await;
+    let cookie = session_cookie(&response);
+
+    let response = test_setup.post_totp_enrolment(&cookie).await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+pub async fn totp_codes_cannot_be_replayed() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (totp, _) = test_setup.enable_totp().await;
+    let code = next_code(&totp);
+    let cookie = test_setup.login_first_step().await;
+    let response = test_setup.post_login_mfa(&cookie, &code).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let cookie = test_setup.login_first_step().await;
+    let response = test_setup.post_login_mfa(&cookie, &code).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn recovery_codes_can_be_used_once() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (_, recovery_codes) = test_setup.enable_totp().await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let cookie = test_setup.login_first_step().await;
+    let response = test_setup.post_login_mfa(&cookie, &recovery_codes[0]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let cookie = test_setup.login_first_step().await;
+    let response = test_setup.post_login_mfa(&cookie, &recovery_codes[0]).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn the_pending_session_ends_after_too_many_wrong_codes() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (totp, _) = test_setup.enable_totp().await;
+    let cookie = test_setup.login_first_step().await;
+
+    for _ in 0..5 {
+        let response = test_setup.post_login_mfa(&cookie, "not-a-code").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    let response = test_setup.post_login_mfa(&cookie, &next_code(&totp)).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn second_factor_requires_a_pending_session() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (totp, recovery_codes) = test_setup.enable_totp().await;
+    let cookie = test_setup.login_first_step().await;
+    let response = test_setup.post_login_mfa(&cookie, &next_code(&totp)).await;
+    // A fully logged in session is not waiting for a code.
+    let cookie = session_cookie(&response);
+
+    let response = test_setup.post_login_mfa(&cookie, &recovery_codes[0]).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,402 @@
+use crate::confirm::get_confirmation_links;
+use crate::dashboard::Browser;
+use crate::login::session_cookie;
+use crate::test_utils;
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const NEW_PASSWORD: &str = "a much better password 42";
+
+impl test_utils::TestSetup {
+    pub async fn put_password(&self, cookie: &str, body: &Value) -> TestResponse {
+        self.client
+            .put("/admin/me/password")
+            .header("Cookie", cookie)
+            .json(body)
+            .send()
+            .await
+    }
+
+    pub async fn post_forgot_password(&self, email: &str) -> TestResponse {
+        self.client
+            .post("/password/forgot")
+            .json(&json!({ "email": email }))
+            .send()
+            .await
+    }
+
+    pub async fn post_reset_password(&self, token: &str, new_password: &str) -> TestResponse {
+        self.client
+            .post("/password/reset")
+            .json(&json!({ "token": token, "new_password": new_password }))
+            .send()
+            .await
+    }
+
+    pub async fn put_email(
+        &self,
+        cookie: &str,
+        current_password: &str,
+        email: &str,
+    ) -> TestResponse {
+        self.client
+            .put("/admin/me/email")
+            .header("Cookie", cookie)
+            .json(&json!({ "current_password": current_password, "email": email }))
+            .send()
+            .await
+    }
+
+    /// Give the test user an email address and return it.
+    pub async fn set_test_user_email(&self) -> String {
+        let email = format!("{}@example.com", self.test_user.username);
+        sqlx::query!(
+            "UPDATE users SET email = $1 WHERE user_id = $2",
+            email,
+            self.test_user.user_id,
+        )
+        .execute(&self.pg_pool)
+        .await
+        .expect("Failed to set the test user email.");
+        email
+    }
+
+    /// Reset emails are sent in the background, wait until `count` have arrived.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<wiremock::Request> {
+        for _ in 0..50 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("Expected {} emails to be sent.", count);
+    }
+
+    /// Request a reset link for the test user and return its path and query.
+    pub async fn request_reset_link(&self) -> String {
+        let email = self.set_test_user_email().await;
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.email_server)
+            .await;
+        let response = self.post_forgot_password(&email).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let email_request = &self.wait_for_emails(1).await[0];
+        let link = reqwest::Url::parse(&get_confirmation_links(email_request)).unwrap();
+        assert_eq!(link.path(), "/password/reset");
+        format!("{}?{}", link.path(), link.query().unwrap())
+    }
+
+    /// Request a reset link for the test user and return its token.
+    pub async fn request_reset_token(&self) -> String {
+        token_of(&self.request_reset_link().await)
+    }
+}
+
+fn token_of(link: &str) -> String {
+    reqwest::Url::parse(&format!("http://localhost{}", link))
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+        .expect("No token in the reset link.")
+}
+
+#[tokio::test]
+pub async fn changing_the_password_requires_the_current_one() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .put_password(
+            &cookie,
+            &json!({"current_password": "wrong password", "new_password": NEW_PASSWORD}),
+        )
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+}
+
+#[tokio::test]
+pub async fn weak_passwords_are_rejected() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    for new_password in ["short1!", "onlyletterslongenough", &"a1".repeat(100)] {
+        let response = test_setup
+            .put_password(
+                &cookie,
+                &json!({
+                    "current_password": test_setup.test_user.password,
+                    "new_password": new_password,
+                }),
+            )
+            .await;
+        assert_eq!(
+            response.status(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{} was accepted",
+            new_password
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn changing_the_password_logs_out_other_sessions() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+    let other_cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .put_password(
+            &cookie,
+            &json!({
+                "current_password": test_setup.test_user.password,
+                "new_password": NEW_PASSWORD,
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    assert_eq!(
+        test_setup.get_admin_me(&cookie).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        test_setup.get_admin_me(&other_cookie).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let response = test_setup
+        .post_login(
+            &test_setup.test_user.username,
+            &test_setup.test_user.password,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = test_setup
+        .post_login(&test_setup.test_user.username, NEW_PASSWORD)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn forgot_password_does_not_reveal_unknown_emails() {
+    let test_setup = test_utils::create_test_setup().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_setup.email_server)
+        .await;
+
+    let response = test_setup.post_forgot_password("nobody@example.com").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn a_failed_audit_entry_does_not_reveal_known_emails() {
+    let test_setup = test_utils::create_test_setup().await;
+    let email = test_setup.set_test_user_email().await;
+    test_setup.reject_audit_entries().await;
+
+    let response = test_setup.post_forgot_password(&email).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM password_reset_tokens"#)
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens, 0, "the token was stored without its audit entry");
+}
+
+#[tokio::test]
+pub async fn reset_links_set_a_new_password_and_end_all_sessions() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+    let token = test_setup.request_reset_token().await;
+
+    let response = test_setup.post_reset_password(&token, NEW_PASSWORD).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        test_setup.get_admin_me(&cookie).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let response = test_setup
+        .post_login(&test_setup.test_user.username, NEW_PASSWORD)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!session_cookie(&response).is_empty());
+}
+
+#[tokio::test]
+pub async fn the_emailed_link_opens_a_form_that_resets_the_password() {
+    let test_setup = test_utils::create_test_setup().await;
+    let link = test_setup.request_reset_link().await;
+    let mut browser = Browser::new(&test_setup);
+
+    let page = browser.get(&link).await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.body.contains(r#"name="new_password""#));
+    let token = token_of(&link);
+    assert!(page
+        .body
+        .contains(&format!(r#"name="token" value="{}""#, token)));
+
+    let page = browser
+        .post(
+            "/dashboard/password/reset",
+            &[("token", &token), ("new_password", NEW_PASSWORD)],
+        )
+        .await;
+    assert_eq!(page.location.as_deref(), Some("/dashboard/login"));
+    let page = browser.get("/dashboard/login").await;
+    assert!(page.body.contains("Your password has been changed"));
+    let response = test_setup
+        .post_login(&test_setup.test_user.username, NEW_PASSWORD)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The link works once.
+    browser.get(&link).await;
+    let page = browser
+        .post(
+            "/dashboard/password/reset",
+            &[
+                ("token", &token),
+                ("new_password", "yet another password 43"),
+            ],
+        )
+        .await;
+    assert_eq!(page.location.as_deref(), Some("/dashboard/login"));
+    let page = browser.get("/dashboard/login").await;
+    assert!(page.body.contains("The reset link is invalid"));
+}
+
+#[tokio::test]
+pub async fn reset_links_work_only_once() {
+    let test_setup = test_utils::create_test_setup().await;
+    let token = test_setup.request_reset_token().await;
+    let response = test_setup.post_reset_password(&token, NEW_PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = test_setup
+        .post_reset_password(&token, "yet another password 43")
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn expired_reset_links_are_rejected() {
+    let test_setup = test_utils::create_test_setup().await;
+    let token = test_setup.request_reset_token().await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 second'")
+        .execute(&test_setup.pg_pool)
+        .await
+        .unwrap();
+
+    let response = test_setup.post_reset_password(&token, NEW_PASSWORD).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+pub async fn two_admins_cannot_share_an_email() {
+    let test_setup = test_utils::create_test_setup().await;
+    let email = test_setup.set_test_user_email().await;
+    let other = test_utils::TestUser::generate();
+    other.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&other).await;
+
+    let response = test_setup.put_email(&cookie, &other.password, &email).await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+pub async fn changing_the_email_requires_the_current_password() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_setup.email_server)
+        .await;
+
+    let response = test_setup
+        .put_email(&cookie, "wrong password", "ada@example.com")
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+async fn stored_email(test_setup: &test_utils::TestSetup) -> Option<String> {
+    sqlx::query_scalar!(
+        "SELECT email FROM users WHERE user_id = $1",
+        test_setup.test_user.user_id
+    )
+    .fetch_one(&test_setup.pg_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+pub async fn a_new_email_is_stored_once_the_emailed_link_is_confirmed() {
+    let test_setup = test_utils::create_test_setup().await;
+    let old_email = test_setup.set_test_user_email().await;
+    let cookie = test_setup.login_test_user().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_setup.email_server)
+        .await;
+
+    let response = test_setup
+        .put_email(&cookie, &test_setup.test_user.password, "ada@example.com")
+        .await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(stored_email(&test_setup).await, Some(old_email));
+
+    let email_request = &test_setup.wait_for_emails(1).await[0];
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "ada@example.com");
+    let link = reqwest::Url::parse(&get_confirmation_links(email_request)).unwrap();
+    assert_eq!(link.path(), "/email/confirm");
+    let link = format!("{}?{}", link.path(), link.query().unwrap());
+
+    let mut browser = Browser::new(&test_setup);
+    let page = browser.get(&link).await;
+    assert_eq!(page.status, StatusCode::OK);
+    let token = token_of(&link);
+    let page = browser
+        .post("/dashboard/email/confirm", &[("token", &token)])
+        .await;
+    assert_eq!(page.location.as_deref(), Some("/dashboard/login"));
+    let page = browser.get("/dashboard/login").await;
+    assert!(page.body.contains("Your email address has been changed."));
+    assert_eq!(
+        stored_email(&test_setup).await.as_deref(),
+        Some("ada@example.com")
+    );
+
+    // The link works once.
+    browser.get(&link).await;
+    browser
+        .post("/dashboard/email/confirm", &[("token", &token)])
+        .await;
+    let page = browser.get("/dashboard/login").await;
+    assert!(page.body.contains("The confirmation link is invalid"));
+}
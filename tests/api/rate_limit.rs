@@ -0,0 +1,224 @@
+use crate::confirm::CorrectQueryParams;
+use crate::subscribe::SubscribeRequest;
+use crate::test_utils::{self, TestSetup};
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::configuration::RateLimitSettings;
+use zero2prod::rate_limit::{self, RateLimitBackend};
+
+impl test_utils::TestSetup {
+    // A subscription by a client behind our proxy on localhost.
+    pub async fn post_subscription_from(&self, ip: &str, email: &str) -> TestResponse {
+        self.client
+            .post("/subscribe")
+            .header("X-Forwarded-For", ip)
+            .json(&SubscribeRequest {
+                email: email.into(),
+                name: "Ursula le Guin".into(),
+            })
+            .send()
+            .await
+    }
+}
+
+async fn create_test_setup(rate_limits: RateLimitSettings) -> TestSetup {
+    let test_setup = test_utils::create_test_setup_with(|configuration| {
+        configuration.rate_limits = RateLimitSettings {
+            enabled: true,
+            trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+            ..rate_limits
+        };
+    })
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_setup.email_server)
+        .await;
+    test_setup
+}
+
+async fn assert_too_many_requests(response: TestResponse) {
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+    let problem: Value = response.json().await;
+    assert_eq!(problem["status"], 429);
+}
+
+#[tokio::test]
+pub async fn subscriptions_are_limited_per_client_ip() {
+    let test_setup = create_test_setup(RateLimitSettings {
+        subscribe_per_ip: 2,
+        subscribe_per_email: 0,
+        ..Default::default()
+    })
+    .await;
+
+    for email in ["ursula@example.com", "ada@example.com"] {
+        let response = test_setup
+            .post_subscription_from("203.0.113.1", email)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = test_setup
+        .post_subscription_from("203.0.113.1", "grace@example.com")
+        .await;
+    assert_too_many_requests(response).await;
+
+    let response = test_setup
+        .post_subscription_from("203.0.113.2", "grace@example.com")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn ipv6_clients_share_the_limit_of_their_64_prefix() {
+    let test_setup = create_test_setup(RateLimitSettings {
+        subscribe_per_ip: 1,
+        subscribe_per_email: 0,
+        ..Default::default()
+    })
+    .await;
+
+    let response = test_setup
+        .post_subscription_from("2001:db8:1:2::1", "ursula@example.com")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test_setup
+        .post_subscription_from("2001:db8:1:2::2", "ada@example.com")
+        .await;
+    assert_too_many_requests(response).await;
+
+    let response = test_setup
+        .post_subscription_from("2001:db8:1:3::1", "ada@example.com")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn subscriptions_are_limited_per_email_across_ips() {
+    let test_setup = create_test_setup(RateLimitSettings {
+        subscribe_per_ip: 0,
+        subscribe_per_email: 1,
+        backend: RateLimitBackend::Postgres,
+        ..Default::default()
+    })
+    .await;
+
+    let response = test_setup
+        .post_subscription_from("203.0.113.1", "ursula@example.com")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test_setup
+        .post_subscription_from("203.0.113.2", "Ursula@Example.com")
+        .await;
+    assert_too_many_requests(response).await;
+
+    let response = test_setup
+        .post_subscription_from("203.0.113.2", "ada@example.com")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The counts are in the database, without the addresses.
+    let keys: Vec<String> = sqlx::query_scalar("SELECT key FROM rate_limits")
+        .fetch_all(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(keys.len(), 2);
+    assert!(keys.iter().all(|key| !key.contains("example.com")));
+}
+
+#[tokio::test]
+pub async fn forwarded_for_is_ignored_without_trusted_proxies() {
+    let test_setup = test_utils::create_test_setup_with(|configuration| {
+        configuration.rate_limits = RateLimitSettings {
+            enabled: true,
+            subscribe_per_ip: 1,
+            subscribe_per_email: 0,
+            ..Default::default()
+        };
+    })
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_setup.email_server)
+        .await;
+
+    let response = test_setup
+        .post_subscription_from("203.0.113.1", "ursula@example.com")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test_setup
+        .post_subscription_from("203.0.113.2", "ada@example.com")
+        .await;
+    assert_too_many_requests(response).await;
+}
+
+#[tokio::test]
+pub async fn confirmations_are_limited_per_client_ip() {
+    let test_setup = create_test_setup(RateLimitSettings {
+        confirm_per_ip: 2,
+        ..Default::default()
+    })
+    .await;
+    let query = CorrectQueryParams {
+        token: "not-a-token".into(),
+    };
+
+    for _ in 0..2 {
+        let response = test_setup.post_confirm(&query).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    assert_too_many_requests(test_setup.post_confirm(&query).await).await;
+}
+
+#[tokio::test]
+pub async fn nothing_is_limited_when_rate_limits_are_disabled() {
+    let test_setup = test_utils::create_test_setup().await;
+    let query = CorrectQueryParams {
+        token: "not-a-token".into(),
+    };
+
+    for _ in 0..RateLimitSettings::default().confirm_per_ip + 1 {
+        let response = test_setup.post_confirm(&query).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+pub async fn postgres_windows_end_and_are_deleted() {
+    let test_setup = create_test_setup(RateLimitSettings {
+        window_seconds: 1,
+        confirm_per_ip: 1,
+        backend: RateLimitBackend::Postgres,
+        ..Default::default()
+    })
+    .await;
+    let query = CorrectQueryParams {
+        token: "not-a-token".into(),
+    };
+
+    assert_eq!(
+        test_setup.post_confirm(&query).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_too_many_requests(test_setup.post_confirm(&query).await).await;
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let deleted = rate_limit::delete_expired(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+    assert_eq!(
+        test_setup.post_confirm(&query).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
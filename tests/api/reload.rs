@@ -0,0 +1,53 @@
+use claim::{assert_err, assert_ok};
+use zero2prod::app::build_state;
+use zero2prod::configuration::get_configuration;
+use zero2prod::reload;
+
+#[tokio::test]
+pub async fn reload_swaps_runtime_settings() {
+    let configuration = get_configuration().expect("Failed to read configuration.");
+    let state = build_state(configuration.clone())
+        .await
+        .expect("Failed to build app state.");
+
+    let mut new_configuration = configuration.clone();
+    new_configuration.email_client.sender = "newsletter@example.com".into();
+    new_configuration.rate_limits.subscribe_per_ip = 1;
+    new_configuration
+        .feature_flags
+        .insert("new_welcome_email".into(), true);
+
+    assert_ok!(reload::apply(&state, &new_configuration));
+
+    let runtime = state.runtime.load();
+    assert_eq!(runtime.rate_limits.subscribe_per_ip, 1);
+    assert!(runtime.feature_enabled("new_welcome_email"));
+    assert_eq!(
+        state.email_client.sender().as_str(),
+        "newsletter@example.com"
+    );
+}
+
+#[tokio::test]
+pub async fn invalid_reload_keeps_previous_settings() {
+    let configuration = get_configuration().expect("Failed to read configuration.");
+    let state = build_state(configuration.clone())
+        .await
+        .expect("Failed to build app state.");
+
+    let mut new_configuration = configuration.clone();
+    new_configuration.email_client.sender = "not-an-email".into();
+    new_configuration.rate_limits.subscribe_per_ip = 1;
+
+    assert_err!(reload::apply(&state, &new_configuration));
+
+    let runtime = state.runtime.load();
+    assert_eq!(
+        runtime.rate_limits.subscribe_per_ip,
+        configuration.rate_limits.subscribe_per_ip
+    );
+    assert_eq!(
+        state.email_client.sender().as_str(),
+        configuration.email_client.sender
+    );
+}
@@ -0,0 +1,58 @@
+use crate::test_utils;
+use axum::http::StatusCode;
+use serde_json::Value;
+use uuid::Uuid;
+
+#[tokio::test]
+pub async fn responses_get_a_generated_request_id() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let first = test_setup.client.get("/health_check").send().await;
+    let second = test_setup.client.get("/health_check").send().await;
+
+    let id = |response: &axum_test_helper::TestResponse| {
+        let value = response.headers()["x-request-id"].to_str().unwrap();
+        Uuid::parse_str(value).expect("The request id is not a UUID.")
+    };
+    assert_ne!(id(&first), id(&second));
+}
+
+#[tokio::test]
+pub async fn the_request_id_of_the_client_is_echoed_on_errors_too() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let requests = [
+        test_setup.client.get("/does-not-exist"),
+        test_setup.client.get("/admin/me"),
+        test_setup
+            .client
+            .post("/subscribe")
+            .json(&serde_json::json!({ "email": "not-an-email", "name": "Ada" })),
+    ];
+    for request in requests {
+        let response = request.header("X-Request-Id", "client-id-7").send().await;
+
+        assert!(response.status().is_client_error());
+        assert_eq!(response.headers()["x-request-id"], "client-id-7");
+    }
+}
+
+#[tokio::test]
+pub async fn the_audit_log_has_the_id_sent_back_to_the_client() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup
+        .post_login(&test_setup.test_user.username, "wrong password")
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request_id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let cookie = test_setup.login_test_user().await;
+    let page: Value = test_setup
+        .get_audit_log(&cookie, "action=login.failure")
+        .await;
+    assert_eq!(page["entries"][0]["request_id"], request_id.as_str());
+}
@@ -0,0 +1,143 @@
+use crate::test_utils::{self, TestUser};
+use axum::http::StatusCode;
+use chrono::Utc;
+use serde_json::{json, Value};
+
+#[tokio::test]
+pub async fn viewers_cannot_edit_subscribers() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&viewer).await;
+
+    let response = test_setup.patch_subscriber(&cookie, id, "Ursula").await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let problem: Value = response.json().await;
+    assert_eq!(problem["status"], 403);
+    assert_eq!(problem["title"], "Forbidden");
+}
+
+#[tokio::test]
+pub async fn viewers_can_list_subscribers() {
+    let test_setup = test_utils::create_test_setup().await;
+    test_setup
+        .insert_subscriber("ursula@example.com", "Ursula", "confirmed", Utc::now())
+        .await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&viewer).await;
+
+    let response = test_setup.get_subscribers(&cookie, "").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let page: Value = response.json().await;
+    assert_eq!(page["subscribers"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+pub async fn editors_can_edit_but_not_delete() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&editor).await;
+
+    let response = test_setup.patch_subscriber(&cookie, id, "Ursula").await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = test_setup
+        .client
+        .delete(&format!("/admin/subscribers/{}", id))
+        .header("Cookie", &cookie)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn only_owners_can_manage_api_keys() {
+    let test_setup = test_utils::create_test_setup().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&editor).await;
+
+    let response = test_setup
+        .post_api_key(
+            &cookie,
+            &json!({"name": "CMS", "scopes": ["subscribers:read"]}),
+        )
+        .await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn owners_can_change_roles() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&test_setup.pg_pool).await;
+    let owner_cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .client
+        .put(&format!("/admin/users/{}/role", viewer.user_id))
+        .header("Cookie", &owner_cookie)
+        .json(&json!({"role": "editor"}))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let cookie = test_setup.login_as(&viewer).await;
+    let response = test_setup.patch_subscriber(&cookie, id, "Ursula").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn non_owners_cannot_change_roles() {
+    let test_setup = test_utils::create_test_setup().await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&test_setup.pg_pool).await;
+    let cookie = test_setup.login_as(&editor).await;
+
+    let response = test_setup
+        .client
+        .put(&format!("/admin/users/{}/role", editor.user_id))
+        .header("Cookie", &cookie)
+        .json(&json!({"role": "owner"}))
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+pub async fn the_last_owner_cannot_be_demoted() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .client
+        .put(&format!(
+            "/admin/users/{}/role",
+            test_setup.test_user.user_id
+        ))
+        .header("Cookie", &cookie)
+        .json(&json!({"role": "viewer"}))
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
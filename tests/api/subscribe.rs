@@ -247,8 +247,8 @@ pub async fn subscribe_sends_a_confirmation_mail_with_a_link() {
         links[0].as_str().to_owned()
     };
 
-    let html_link = get_link(&req_body["HtmlBody"].as_str().unwrap());
-    let text_link = get_link(&req_body["TextBody"].as_str().unwrap());
+    let html_link = get_link(req_body["HtmlBody"].as_str().unwrap());
+    let text_link = get_link(req_body["TextBody"].as_str().unwrap());
     // The two links should be identical
     assert_eq!(html_link, text_link);
 }
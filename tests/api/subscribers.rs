@@ -0,0 +1,516 @@
+use crate::confirm::get_confirmation_links;
+use crate::test_utils::{self, TestUser};
+use axum::http::StatusCode;
+use axum_test_helper::TestResponse;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+impl test_utils::TestSetup {
+    pub async fn get_subscribers(&self, cookie: &str, query: &str) -> TestResponse {
+        self.client
+            .get(&format!("/admin/subscribers?{}", query))
+            .header("Cookie", cookie)
+            .send()
+            .await
+    }
+
+    /// Insert a subscriber directly into the database and return its id.
+    pub async fn insert_subscriber(
+        &self,
+        email: &str,
+        name: &str,
+        status: &str,
+        subscribed_at: DateTime<Utc>,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+            VALUES ($1, $2, $3, $4, $5)",
+            id,
+            email,
+            name,
+            subscribed_at,
+            status,
+        )
+        .execute(&self.pg_pool)
+        .await
+        .expect("Failed to insert subscriber.");
+        id
+    }
+
+    /// Follow `next_cursor` until the last page, returning the emails in order.
+    pub async fn list_all_emails(&self, cookie: &str, query: &str) -> Vec<String> {
+        let mut emails = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page_query = match &cursor {
+                Some(cursor) => format!("{}&cursor={}", query, cursor),
+                None => query.to_string(),
+            };
+            let response = self.get_subscribers(cookie, &page_query).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let page: Value = response.json().await;
+            emails.extend(emails_of(&page));
+            match page["next_cursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => return emails,
+            }
+        }
+    }
+}
+
+fn emails_of(page: &Value) -> Vec<String> {
+    page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["email"].as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn setup_with_subscribers() -> (test_utils::TestSetup, String) {
+    let test_setup = test_utils::create_test_setup().await;
+    let now = Utc::now();
+    for (i, (email, name, status)) in [
+        ("ada@example.com", "Ada Lovelace", "confirmed"),
+        ("grace@example.com", "Grace Hopper", "confirmed"),
+        (
+            "ursula@example.com",
+            "Ursula le Guin",
+            "pending_confirmation",
+        ),
+        ("alan@example.com", "Alan Turing", "confirmed"),
+        (
+            "octavia@example.com",
+            "Octavia Butler",
+            "pending_confirmation",
+        ),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        test_setup
+            .insert_subscriber(email, name, status, now - Duration::days(i as i64))
+            .await;
+    }
+    let cookie = test_setup.login_test_user().await;
+    (test_setup, cookie)
+}
+
+#[tokio::test]
+pub async fn subscribers_are_listed_newest_first_with_counts() {
+    let (test_setup, cookie) = setup_with_subscribers().await;
+
+    let response = test_setup.get_subscribers(&cookie, "").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let page: Value = response.json().await;
+    assert_eq!(
+        emails_of(&page),
+        [
+            "ada@example.com",
+            "grace@example.com",
+            "ursula@example.com",
+            "alan@example.com",
+            "octavia@example.com"
+        ]
+    );
+    assert_eq!(page["counts"]["confirmed"], 3);
+    assert_eq!(page["counts"]["pending_confirmation"], 2);
+    assert!(page["next_cursor"].is_null());
+}
+
+#[tokio::test]
+pub async fn subscribers_can_be_filtered_by_status_and_date() {
+    let (test_setup, cookie) = setup_with_subscribers().await;
+    let from = (Utc::now() - Duration::hours(36)).to_rfc3339();
+
+    let page: Value = test_setup
+        .get_subscribers(
+            &cookie,
+            &format!("status=confirmed&subscribed_from={}", urlencoding(&from)),
+        )
+        .await
+        .json()
+        .await;
+
+    assert_eq!(emails_of(&page), ["ada@example.com", "grace@example.com"]);
+    // Counts ignore the status filter but not the others.
+    assert_eq!(page["counts"]["confirmed"], 2);
+    assert_eq!(page["counts"]["pending_confirmation"], 0);
+}
+
+#[tokio::test]
+pub async fn search_matches_email_or_name_case_insensitively() {
+    let (test_setup, cookie) = setup_with_subscribers().await;
+
+    let page: Value = test_setup
+        .get_subscribers(&cookie, "q=HOPPER&sort=email&order=asc")
+        .await
+        .json()
+        .await;
+    assert_eq!(emails_of(&page), ["grace@example.com"]);
+
+    let page: Value = test_setup
+        .get_subscribers(&cookie, "q=LA&sort=email&order=asc")
+        .await
+        .json()
+        .await;
+    assert_eq!(
+        emails_of(&page),
+        ["ada@example.com", "alan@example.com", "ursula@example.com"]
+    );
+}
+
+#[tokio::test]
+pub async fn search_wildcards_are_matched_literally() {
+    let (test_setup, cookie) = setup_with_subscribers().await;
+
+    let page: Value = test_setup
+        .get_subscribers(&cookie, "q=%25")
+        .await
+        .json()
+        .await;
+
+    assert!(emails_of(&page).is_empty());
+}
+
+#[tokio::test]
+pub async fn pages_cover_every_subscriber_exactly_once() {
+    let (test_setup, cookie) = setup_with_subscribers().await;
+
+    let emails = test_setup
+        .list_all_emails(&cookie, "sort=email&order=asc&limit=2")
+        .await;
+
+    assert_eq!(
+        emails,
+        [
+            "ada@example.com",
+            "alan@example.com",
+            "grace@example.com",
+            "octavia@example.com",
+            "ursula@example.com"
+        ]
+    );
+}
+
+#[tokio::test]
+pub async fn counts_are_only_on_the_first_page() {
+    let (test_setup, cookie) = setup_with_subscribers().await;
+
+    let response = test_setup.get_subscribers(&cookie, "limit=2").await;
+    let first: Value = response.json().await;
+    assert_eq!(first["counts"]["confirmed"], 3);
+
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let response = test_setup
+        .get_subscribers(&cookie, &format!("limit=2&cursor={}", cursor))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let second: Value = response.json().await;
+    assert_eq!(second["subscribers"].as_array().unwrap().len(), 2);
+    assert!(second.get("counts").is_none());
+}
+
+#[tokio::test]
+pub async fn pagination_handles_equal_sort_keys() {
+    let test_setup = test_utils::create_test_setup().await;
+    let subscribed_at = Utc::now();
+    for i in 0..7 {
+        test_setup
+            .insert_subscriber(
+                &format!("same-time-{}@example.com", i),
+                "Same Time",
+                "confirmed",
+                subscribed_at,
+            )
+            .await;
+    }
+    let cookie = test_setup.login_test_user().await;
+
+    let mut emails = test_setup.list_all_emails(&cookie, "limit=3").await;
+
+    assert_eq!(emails.len(), 7);
+    emails.sort();
+    emails.dedup();
+    assert_eq!(emails.len(), 7);
+}
+
+#[tokio::test]
+pub async fn invalid_cursors_and_filters_are_rejected() {
+    let (test_setup, cookie) = setup_with_subscribers().await;
+
+    let response = test_setup
+        .get_subscribers(&cookie, "cursor=not-a-cursor")
+        .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    for query in ["status=deleted", "sort=id", "limit=1000"] {
+        let response = test_setup.get_subscribers(&cookie, query).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+pub async fn listing_subscribers_requires_a_session() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    let response = test_setup.client.get("/admin/subscribers").send().await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+fn urlencoding(s: &str) -> String {
+    serde_urlencoded::to_string([("v", s)]).unwrap()[2..].to_string()
+}
+
+impl test_utils::TestSetup {
+    /// Insert a subscriber waiting for confirmation, and its token.
+    pub async fn insert_pending_subscriber(&self, email: &str) -> (Uuid, String) {
+        let id = self
+            .insert_subscriber(
+                email,
+                "Pending Subscriber",
+                "pending_confirmation",
+                Utc::now(),
+            )
+            .await;
+        let token = Uuid::new_v4().simple().to_string();
+        sqlx::query!(
+            "INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ($1, $2)",
+            token,
+            id,
+        )
+        .execute(&self.pg_pool)
+        .await
+        .expect("Failed to insert subscription token.");
+        (id, token)
+    }
+
+    pub async fn post_subscriber_action(
+        &self,
+        cookie: &str,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> TestResponse {
+        self.client
+            .post(&format!("/admin/subscribers/{}/{}", subscriber_id, action))
+            .header("Cookie", cookie)
+            .send()
+            .await
+    }
+
+    pub async fn patch_subscriber(
+        &self,
+        cookie: &str,
+        subscriber_id: Uuid,
+        name: &str,
+    ) -> TestResponse {
+        self.client
+            .patch(&format!("/admin/subscribers/{}", subscriber_id))
+            .header("Cookie", cookie)
+            .json(&serde_json::json!({ "name": name }))
+            .send()
+            .await
+    }
+
+    pub async fn get_subscriber(&self, cookie: &str, subscriber_id: Uuid) -> TestResponse {
+        self.client
+            .get(&format!("/admin/subscribers/{}", subscriber_id))
+            .header("Cookie", cookie)
+            .send()
+            .await
+    }
+}
+
+#[tokio::test]
+pub async fn a_subscriber_is_shown_with_tokens_and_history() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, token) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+    test_setup
+        .client
+        .patch(&format!("/admin/subscribers/{}", id))
+        .header("Cookie", &cookie)
+        .json(&serde_json::json!({ "name": "Ursula K. Le Guin" }))
+        .send()
+        .await;
+
+    let response = test_setup.get_subscriber(&cookie, id).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["subscriber"]["email"], "ursula@example.com");
+    assert_eq!(body["subscriber"]["name"], "Ursula K. Le Guin");
+    assert_eq!(body["tokens"]["count"], 1);
+    assert!(body["tokens"]["last_created_at"].is_string());
+    assert!(!body.to_string().contains(&token));
+    assert_eq!(body["history"][0]["action"], "subscriber.update");
+}
+
+#[tokio::test]
+pub async fn invalid_names_are_rejected() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .client
+        .patch(&format!("/admin/subscribers/{}", id))
+        .header("Cookie", &cookie)
+        .json(&serde_json::json!({ "name": "<script>" }))
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+pub async fn subscribers_can_be_confirmed_by_hand() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .post_subscriber_action(&cookie, id, "confirm")
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await;
+    assert_eq!(body["status"], "confirmed");
+}
+
+#[tokio::test]
+pub async fn unsubscribed_subscribers_cannot_confirm_with_an_old_link() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, token) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .post_subscriber_action(&cookie, id, "unsubscribe")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = test_setup
+        .client
+        .post(&format!("/confirm?token={}", token))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test_setup.get_subscriber(&cookie, id).await.json().await;
+    assert_eq!(body["subscriber"]["status"], "unsubscribed");
+}
+
+#[tokio::test]
+pub async fn the_confirmation_email_can_be_resent_to_pending_subscribers() {
+    let test_setup = test_utils::create_test_setup().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_setup.email_server)
+        .await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .post_subscriber_action(&cookie, id, "resend_confirmation")
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // The new link confirms the subscriber.
+    let email_request = &test_setup.email_server.received_requests().await.unwrap()[0];
+    let link = reqwest::Url::parse(&get_confirmation_links(email_request)).unwrap();
+    let response = test_setup
+        .client
+        .post(&format!("{}?{}", link.path(), link.query().unwrap()))
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = test_setup
+        .post_subscriber_action(&cookie, id, "resend_confirmation")
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+pub async fn deleting_a_subscriber_removes_its_tokens() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let cookie = test_setup.login_test_user().await;
+
+    let response = test_setup
+        .client
+        .delete(&format!("/admin/subscribers/{}", id))
+        .header("Cookie", &cookie)
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = test_setup.get_subscriber(&cookie, id).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let tokens = sqlx::query!("SELECT count(*) AS \"count!\" FROM subscription_tokens")
+        .fetch_one(&test_setup.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens.count, 0);
+}
+
+#[tokio::test]
+pub async fn only_owners_can_delete_and_viewers_cannot_edit() {
+    let test_setup = test_utils::create_test_setup().await;
+    let (id, _) = test_setup
+        .insert_pending_subscriber("ursula@example.com")
+        .await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&test_setup.pg_pool).await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&test_setup.pg_pool).await;
+
+    let cookie = test_setup.login_as(&editor).await;
+    let response = test_setup
+        .client
+        .delete(&format!("/admin/subscribers/{}", id))
+        .header("Cookie", &cookie)
+        .send()
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let cookie = test_setup.login_as(&viewer).await;
+    let response = test_setup
+        .post_subscriber_action(&cookie, id, "confirm")
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = test_setup.get_subscriber(&cookie, id).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn actions_on_unknown_subscribers_are_not_found() {
+    let test_setup = test_utils::create_test_setup().await;
+    let cookie = test_setup.login_test_user().await;
+
+    for action in ["confirm", "unsubscribe", "resend_confirmation"] {
+        let response = test_setup
+            .post_subscriber_action(&cookie, Uuid::new_v4(), action)
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", action);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::confirm::CorrectQueryParams;
+use crate::subscribe::SubscribeRequest;
+use crate::test_utils;
+use axum::http::StatusCode;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Registry;
+use wiremock::http::HeaderName;
+use wiremock::matchers::{any, header, header_exists, method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+use zero2prod::configuration::TelemetrySettings;
+use zero2prod::telemetry::{get_subscriber, otlp_tracer, shutdown_tracer, LogFormat, LogWriter};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+// Matches a `traceparent` in the trace of the caller, from a span of our own.
+struct ContinuesTrace;
+impl wiremock::Match for ContinuesTrace {
+    fn matches(&self, request: &Request) -> bool {
+        let Some(traceparent) = request.headers.get(&HeaderName::from("traceparent")) else {
+            return false;
+        };
+        let parts: Vec<&str> = traceparent.last().as_str().split('-').collect();
+        matches!(parts[..], ["00", trace_id, span_id, "01"]
+            if trace_id == TRACE_ID && span_id != PARENT_SPAN_ID)
+    }
+}
+
+#[tokio::test]
+pub async fn the_trace_of_the_caller_continues_to_the_email_provider() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    Mock::given(path("/email"))
+        .and(ContinuesTrace)
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_setup.email_server)
+        .await;
+
+    let response = test_setup
+        .client
+        .post("/subscribe")
+        .header(
+            "traceparent",
+            format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID),
+        )
+        .json(&serde_json::json!({ "email": "ada@example.com", "name": "Ada" }))
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn an_invalid_traceparent_starts_a_new_trace() {
+    let test_setup = test_utils::create_test_setup().await;
+
+    Mock::given(path("/email"))
+        .and(header_exists("traceparent"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_setup.email_server)
+        .await;
+
+    let response = test_setup
+        .client
+        .post("/subscribe")
+        .header("traceparent", "not-a-traceparent")
+        .json(&serde_json::json!({ "email": "ada@example.com", "name": "Ada" }))
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// The exporter blocks on shutdown until the batch is sent, which needs a second thread.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn spans_are_exported_to_the_otlp_endpoint() {
+    let collector = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/traces"))
+        .and(header("Content-Type", "application/x-protobuf"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1..)
+        .mount(&collector)
+        .await;
+    let settings = TelemetrySettings {
+        otlp_endpoint: Some(format!("{}/v1/traces", collector.uri())),
+        ..TelemetrySettings::default()
+    };
+
+    let tracer = otlp_tracer(&settings)
+        .expect("Failed to create the tracer.")
+        .expect("No tracer although an endpoint is set.");
+    let (subscriber, _) = get_subscriber(
+        "test".into(),
+        "info".into(),
+        LogFormat::Compact,
+        LogWriter::Discard,
+        Some(tracer),
+    );
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("exported").in_scope(|| tracing::info!("inside"));
+    });
+    tokio::task::spawn_blocking(shutdown_tracer).await.unwrap();
+}
+
+#[test]
+pub fn there_is_no_tracer_without_an_endpoint() {
+    let tracer = otlp_tracer(&TelemetrySettings::default()).unwrap();
+    assert!(tracer.is_none());
+}
+
+// Collects the log lines written on this thread, as they are written in production.
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = Self;
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+// The test runtime has a single thread, so the app logs to the subscriber of the test.
+#[tokio::test]
+pub async fn failed_queries_do_not_log_personal_data() {
+    let test_setup = test_utils::create_test_setup().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_setup.email_server)
+        .await;
+    let logs = CapturedLogs::default();
+    let subscriber = Registry::default()
+        .with(JsonStorageLayer)
+        .with(BunyanFormattingLayer::new("test".into(), logs.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let body = SubscribeRequest {
+        email: "grace.hopper@example.com".into(),
+        name: "Grace Hopper".into(),
+    };
+    let response = test_setup.post_subscriptions(&body).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // The address is taken, so the insert fails on the unique constraint.
+    let response = test_setup.post_subscriptions(&body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let logs = logs.contents();
+    assert!(logs.contains("database error 23505"), "{}", logs);
+    assert!(!logs.contains("grace.hopper"), "{}", logs);
+    assert!(!logs.contains("Grace Hopper"), "{}", logs);
+}
+
+#[tokio::test]
+pub async fn query_strings_are_not_logged() {
+    let test_setup = test_utils::create_test_setup().await;
+    let logs = CapturedLogs::default();
+    let subscriber = Registry::default()
+        .with(JsonStorageLayer)
+        .with(BunyanFormattingLayer::new("test".into(), logs.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let response = test_setup
+        .post_confirm(&CorrectQueryParams {
+            token: "secretconfirmationtoken123".into(),
+        })
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let logs = logs.contents();
+    assert!(logs.contains("/confirm"), "{}", logs);
+    assert!(!logs.contains("secretconfirmationtoken123"), "{}", logs);
+}
@@ -1,60 +1,101 @@
+use axum::extract::connect_info::MockConnectInfo;
 use axum_test_helper::TestClient;
 use once_cell::sync::Lazy;
+use opentelemetry::sdk::trace::{Tracer, TracerProvider};
+use opentelemetry::trace::TracerProvider as _;
+use secrecy::Secret;
 use sqlx::{Executor, PgPool}; // Connection,
-use std;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use tracing::info;
 use uuid::Uuid;
 use wiremock::MockServer;
 use zero2prod::app::spawn_app;
+use zero2prod::authentication::compute_password_hash;
 use zero2prod::configuration::get_configuration;
-use zero2prod::configuration::DatabaseSettings;
-use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::configuration::{DatabaseSettings, Settings};
+use zero2prod::telemetry::{get_subscriber, init_subscriber, LogFormat, LogWriter};
 
-// use tracing::subscriber::set_global_default;
-use tracing::Subscriber;
-use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
-// use tracing_log::LogTracer;
-use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
+// Spans get OpenTelemetry ids like in production, so `traceparent` propagation can be
+// tested, but are not exported. The tracer only works while its provider is alive.
+static TRACER_PROVIDER: Lazy<TracerProvider> = Lazy::new(|| TracerProvider::builder().build());
+
+pub fn test_tracer() -> Tracer {
+    TRACER_PROVIDER.tracer("test")
+}
 
 // Ensure that the `tracing` stack is only initialised once using `once_cell`
 static TRACING: Lazy<()> = Lazy::new(|| {
-    let default_filter_level = "info".to_string();
-    let subscriber_name = "test".to_string();
-    // We cannot assign the output of `get_subscriber` to a variable based on the value of `TEST_LOG`
-
     // If TEST_LOG is set, output the logs to stdout, else do not show them.
-    // because the sink is part of the type returned by `get_subscriber`, therefore they are not the
-    // same type. We could work around it, but this is the most straight-forward way of moving forward.
-    if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level);
-        init_subscriber(subscriber);
+    let writer = if std::env::var("TEST_LOG").is_ok() {
+        LogWriter::Stdout
     } else {
-        let subscriber = get_sink_subscriber(subscriber_name, default_filter_level);
-        init_subscriber(subscriber);
+        LogWriter::Discard
     };
+    let (subscriber, log_filter) = get_subscriber(
+        "test".into(),
+        "info".into(),
+        LogFormat::Json,
+        writer,
+        Some(test_tracer()),
+    );
+    init_subscriber(subscriber, log_filter);
 });
 
-// Could not get this to work as a parameter in the function above, so just copied it
-// this version sends the traces to std::io::sink, for use in tests.
-pub fn get_sink_subscriber(name: String, env_filter: String) -> impl Subscriber + Send + Sync {
-    let env_filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, std::io::sink);
-    Registry::default()
-        .with(env_filter)
-        .with(JsonStorageLayer)
-        .with(formatting_layer)
-}
+// Scrapes `/metrics` in the tests.
+pub const METRICS_TOKEN: &str = "test-metrics-token";
 
 pub struct TestSetup {
     pub client: TestClient,
     pub pg_pool: PgPool,
     pub email_server: MockServer,
+    pub test_user: TestUser,
+}
+
+// An admin user stored in the test database, with its plain text password.
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub password: String,
+    pub role: String,
+}
+
+impl TestUser {
+    pub fn generate() -> Self {
+        Self::with_role("owner")
+    }
+
+    pub fn with_role(role: &str) -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+            role: role.to_string(),
+        }
+    }
+
+    pub async fn store(&self, pool: &PgPool) {
+        let password_hash = compute_password_hash(Secret::new(self.password.clone()))
+            .expect("Failed to hash test user password.");
+        sqlx::query!(
+            "INSERT INTO users (user_id, username, password_hash, role) VALUES ($1, $2, $3, $4)",
+            self.user_id,
+            self.username,
+            secrecy::ExposeSecret::expose_secret(&password_hash),
+            self.role,
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to store test user.");
+    }
 }
 
-#[tracing::instrument()]
 pub async fn create_test_setup() -> TestSetup {
+    create_test_setup_with(|_| {}).await
+}
+
+// Like `create_test_setup`, with settings changed by `configure` first.
+#[tracing::instrument(skip(configure))]
+pub async fn create_test_setup_with(configure: impl FnOnce(&mut Settings)) -> TestSetup {
     // The first time `initialize` is invoked the code in `TRACING` is executed.
     // All other invocations will instead skip execution.
     Lazy::force(&TRACING);
@@ -85,6 +126,8 @@ pub async fn create_test_setup() -> TestSetup {
     );
 
     configuration.email_client.base_url = format!("http://{}:{:?}", remove_quotes(&ip), port);
+    configuration.metrics.bearer_token = Some(Secret::new(METRICS_TOKEN.into()));
+    configure(&mut configuration);
 
     // Spawn the app with the newly created db.
     // Can I get the pool back from the app? Now I'm creating multiple pools.
@@ -92,7 +135,9 @@ pub async fn create_test_setup() -> TestSetup {
     let app = spawn_app(configuration.clone())
         .await
         .expect("Failed to spawn app.");
-    let client = TestClient::new(app);
+    // Requests come from localhost, like through `shutdown::serve`.
+    let client =
+        TestClient::new(app.layer(MockConnectInfo(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))));
 
     // This pool is required to directly check the result of database operations.
     info!("Creating extra postgres connection pool for checking database operations.");
@@ -101,10 +146,14 @@ pub async fn create_test_setup() -> TestSetup {
         .await
         .expect("Failed to connect to Postgres");
 
+    let test_user = TestUser::generate();
+    test_user.store(&pg_pool).await;
+
     TestSetup {
         client,
         pg_pool,
         email_server,
+        test_user,
     }
 }
 
@@ -0,0 +1,133 @@
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use axum_test_helper::TestClient;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+use zero2prod::configuration::TlsSettings;
+use zero2prod::shutdown::{self, CancellationToken};
+use zero2prod::tls;
+
+// A self-signed certificate for `localhost`, written to `dir`. Returns its PEM.
+fn write_certificate(dir: &Path) -> String {
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let pem = certificate.serialize_pem().unwrap();
+    std::fs::write(dir.join("tls.key"), certificate.serialize_private_key_pem()).unwrap();
+    std::fs::write(dir.join("tls.crt"), &pem).unwrap();
+    pem
+}
+
+fn settings(dir: &Path) -> TlsSettings {
+    TlsSettings {
+        cert_path: dir.join("tls.crt"),
+        key_path: dir.join("tls.key"),
+        redirect_port: None,
+    }
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zero2prod-tls-{}", Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    dir
+}
+
+// A client that only trusts `certificate_pem`.
+async fn get_with(certificate_pem: &str, addr: SocketAddr) -> reqwest::Result<String> {
+    reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(certificate_pem.as_bytes()).unwrap())
+        .resolve("localhost", addr)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap()
+        .get(format!("https://localhost:{}/", addr.port()))
+        .send()
+        .await?
+        .text()
+        .await
+}
+
+#[tokio::test]
+pub async fn https_uses_the_certificate_and_picks_up_a_new_one() {
+    let dir = temp_dir();
+    let first = write_certificate(&dir);
+    let config = tls::load(&settings(&dir)).await.unwrap();
+    tls::spawn_reloader(config.clone(), settings(&dir)).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/", get(|| async { "secure" }));
+    let shutdown = CancellationToken::new();
+    tokio::spawn(shutdown::serve(
+        listener,
+        app,
+        Some(config),
+        shutdown.clone(),
+        Duration::from_secs(1),
+    ));
+
+    assert_eq!(get_with(&first, addr).await.unwrap(), "secure");
+
+    let second = write_certificate(&dir);
+    let mut reloaded = false;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if get_with(&second, addr).await.is_ok() {
+            reloaded = true;
+            break;
+        }
+    }
+    assert!(reloaded, "The new certificate was not loaded.");
+    assert!(get_with(&first, addr).await.is_err());
+
+    shutdown.cancel();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+pub async fn missing_or_invalid_certificates_are_reported() {
+    let dir = temp_dir();
+    assert!(tls::load(&settings(&dir)).await.is_err());
+
+    std::fs::write(dir.join("tls.crt"), "not a certificate").unwrap();
+    std::fs::write(dir.join("tls.key"), "not a key").unwrap();
+    let error = tls::load(&settings(&dir)).await.unwrap_err();
+    assert!(error.contains("tls.crt"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+pub async fn plain_http_is_redirected_to_https() {
+    let client = TestClient::new(tls::redirect_router(8443));
+
+    let response = client
+        .get("/confirm?token=abc")
+        .header("Host", "news.example.com:8080")
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        response.headers()["Location"],
+        "https://news.example.com:8443/confirm?token=abc"
+    );
+}
+
+#[tokio::test]
+pub async fn the_default_https_port_is_left_out_of_the_redirect() {
+    let client = TestClient::new(tls::redirect_router(443));
+
+    let response = client
+        .post("/subscribe")
+        .header("Host", "news.example.com")
+        .send()
+        .await;
+
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        response.headers()["Location"],
+        "https://news.example.com/subscribe"
+    );
+}